@@ -0,0 +1,66 @@
+// Cost of turning a client frame into the keys to send: parse the JSON,
+// map it to an action and look the chord up in the resolved binding table,
+// against the old per-input string building and key parsing.
+//
+//     cargo bench --no-default-features --features enigo-output --bench binding_table
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use mobileherolauncher_lib::bindings::{Action, BindingTable};
+use mobileherolauncher_lib::keyboard::{self, KeyOutput};
+use mobileherolauncher_lib::{ClientMessage, Config};
+
+const FRAMES: [&str; 6] = [
+    r#"{"type":"fret","value":"green","pressed":true}"#,
+    r#"{"type":"fret","value":"orange","pressed":false}"#,
+    r#"{"type":"strum","value":"up","pressed":true}"#,
+    r#"{"type":"strum","direction":"down","pressed":true}"#,
+    r#"{"type":"drum","value":"kick","pressed":true}"#,
+    r#"{"type":"drum","value":"red","pressed":true}"#,
+];
+
+// What the server did per input before the binding table
+fn legacy_lookup(config: &Config, frame: &str) -> Option<Vec<KeyOutput>> {
+    let message: serde_json::Value = serde_json::from_str(frame).ok()?;
+    let msg_type = message["type"].as_str()?.to_string();
+    let value = message
+        .get("value")
+        .or(message.get("direction"))
+        .and_then(|v| v.as_str())?
+        .to_string();
+    let name = match msg_type.as_str() {
+        "fret" => value,
+        other => format!("{}_{}", other, value),
+    };
+    let binding = config.key_bindings.get(&name)?.clone();
+    keyboard::string_to_keys(&binding.to_lowercase())
+}
+
+fn table_lookup<'t>(table: &'t BindingTable<Vec<KeyOutput>>, frame: &str) -> Option<&'t [KeyOutput]> {
+    let message: ClientMessage = serde_json::from_str(frame).ok()?;
+    let action = Action::from_message(message.msg_type, message.value())?;
+    table.get(action).map(Vec::as_slice)
+}
+
+fn bench_lookup(c: &mut Criterion) {
+    let config = Config::default();
+    let (table, _) = BindingTable::resolve(&config, keyboard::string_to_keys);
+
+    c.bench_function("legacy string lookup", |b| {
+        b.iter(|| {
+            for frame in FRAMES {
+                black_box(legacy_lookup(&config, black_box(frame)));
+            }
+        })
+    });
+
+    c.bench_function("action table lookup", |b| {
+        b.iter(|| {
+            for frame in FRAMES {
+                black_box(table_lookup(&table, black_box(frame)));
+            }
+        })
+    });
+}
+
+criterion_group!(benches, bench_lookup);
+criterion_main!(benches);
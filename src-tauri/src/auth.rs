@@ -0,0 +1,307 @@
+use hmac::{Hmac, Mac};
+use rand::{Rng, RngCore};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+type HmacSha256 = Hmac<Sha256>;
+
+const NONCE_LEN: usize = 32;
+const SESSION_PIN_LEN: usize = 6;
+const SALT_LEN: usize = 16;
+const HASH_LEN: usize = 32;
+const PBKDF2_ITERATIONS: u32 = 100_000;
+
+// Failed attempts allowed before the first lockout kicks in
+const FREE_ATTEMPTS: u32 = 3;
+// First lockout duration, doubled on every further failure
+const BASE_LOCKOUT: Duration = Duration::from_secs(5);
+const MAX_LOCKOUT: Duration = Duration::from_secs(15 * 60);
+// Failure history is dropped once a key has been quiet for this long
+const FORGET_AFTER: Duration = Duration::from_secs(60 * 60);
+
+struct FailureRecord {
+    failures: u32,
+    locked_until: Option<Instant>,
+    last_failure: Instant,
+}
+
+// Tracks failed PIN attempts per key (the remote IP) and applies
+// exponential backoff once FREE_ATTEMPTS is exceeded.
+#[derive(Default)]
+pub struct AuthLimiter {
+    records: HashMap<String, FailureRecord>,
+}
+
+impl AuthLimiter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Returns the remaining lockout if any of the keys is currently locked.
+    pub fn check(&mut self, keys: &[String]) -> Option<Duration> {
+        let now = Instant::now();
+        self.prune(now);
+
+        keys.iter()
+            .filter_map(|k| self.records.get(k))
+            .filter_map(|r| r.locked_until)
+            .filter(|until| *until > now)
+            .map(|until| until - now)
+            .max()
+    }
+
+    // Starts an attempt: fails with the remaining lockout if any key is
+    // locked, otherwise counts the attempt as failed right away. Checking and
+    // counting under one lock keeps parallel connections from all getting
+    // past the check while the first is still verifying; `record_success`
+    // clears the attempt again. Returns the lockout that applies if the
+    // attempt does fail.
+    pub fn begin_attempt(&mut self, keys: &[String]) -> Result<Option<Duration>, Duration> {
+        match self.check(keys) {
+            Some(wait) => Err(wait),
+            None => Ok(self.record_failure(keys)),
+        }
+    }
+
+    // Records a failed attempt for every key. Returns the lockout that now
+    // applies, if the failure pushed any key over the limit.
+    pub fn record_failure(&mut self, keys: &[String]) -> Option<Duration> {
+        let now = Instant::now();
+        let mut lockout = None;
+
+        for key in keys {
+            let record = self.records.entry(key.clone()).or_insert(FailureRecord {
+                failures: 0,
+                locked_until: None,
+                last_failure: now,
+            });
+            record.failures += 1;
+            record.last_failure = now;
+
+            if record.failures > FREE_ATTEMPTS {
+                let exponent = (record.failures - FREE_ATTEMPTS - 1).min(16);
+                let duration = BASE_LOCKOUT.saturating_mul(1 << exponent).min(MAX_LOCKOUT);
+                record.locked_until = Some(now + duration);
+                lockout = lockout.max(Some(duration));
+            }
+        }
+
+        lockout
+    }
+
+    pub fn record_success(&mut self, keys: &[String]) {
+        for key in keys {
+            self.records.remove(key);
+        }
+    }
+
+    fn prune(&mut self, now: Instant) {
+        self.records.retain(|_, r| {
+            let locked = r.locked_until.is_some_and(|until| until > now);
+            locked || now.duration_since(r.last_failure) < FORGET_AFTER
+        });
+    }
+}
+
+// Keys a connection is tracked under. Only its IP: the device id in the auth
+// message is whatever the client claims, so keying on it would let anyone
+// lock a victim's phone out by sending its id.
+pub fn limiter_keys(ip: std::net::IpAddr) -> Vec<String> {
+    vec![format!("ip:{}", ip)]
+}
+
+// Random challenge sent to every client on connect, hex encoded.
+pub fn generate_nonce() -> String {
+    let mut bytes = [0u8; NONCE_LEN];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+// Random session PIN with its hash, computed off the async workers so
+// callers only need the server lock to install it
+pub async fn generate_session_pin() -> (String, PinHash) {
+    let pin = generate_pin();
+    let hash = PinHash::new_blocking(pin.clone()).await;
+    (pin, hash)
+}
+
+// Random numeric PIN for a single server session.
+pub fn generate_pin() -> String {
+    let mut rng = rand::thread_rng();
+    (0..SESSION_PIN_LEN)
+        .map(|_| char::from(b'0' + rng.gen_range(0..10u8)))
+        .collect()
+}
+
+// Salted PBKDF2-HMAC-SHA256 of a PIN, as stored in config.json. The derived
+// key is also the HMAC key for the challenge-response handshake, so `hash`
+// is a credential in its own right: whoever can read it can authenticate
+// without knowing the PIN. Treat it like a password (config.json is written
+// owner-only). The salt and iteration count are sent to every client, so a
+// sniffed challenge and response let an attacker brute-force a short PIN
+// offline; the handshake only keeps the PIN off the wire in cleartext.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PinHash {
+    pub salt: String,
+    pub iterations: u32,
+    pub hash: String,
+}
+
+impl PinHash {
+    pub fn new(pin: &str) -> Self {
+        let mut salt = [0u8; SALT_LEN];
+        rand::thread_rng().fill_bytes(&mut salt);
+        Self::with_salt(pin, &salt, PBKDF2_ITERATIONS)
+    }
+
+    fn with_salt(pin: &str, salt: &[u8], iterations: u32) -> Self {
+        Self {
+            salt: hex::encode(salt),
+            iterations,
+            hash: hex::encode(derive_key(pin, salt, iterations)),
+        }
+    }
+
+    // `new` on the blocking pool: PBKDF2 takes long enough to stall a tokio
+    // worker
+    pub async fn new_blocking(pin: String) -> Self {
+        tokio::task::spawn_blocking(move || Self::new(&pin))
+            .await
+            .expect("PIN hashing panicked")
+    }
+
+    // `verify` on the blocking pool, for the same reason
+    pub async fn verify_blocking(&self, pin: &str) -> bool {
+        let (hash, pin) = (self.clone(), pin.to_string());
+        tokio::task::spawn_blocking(move || hash.verify(&pin))
+            .await
+            .unwrap_or(false)
+    }
+
+    // Legacy plain-PIN path: re-derives and compares in constant time.
+    pub fn verify(&self, pin: &str) -> bool {
+        let Ok(salt) = hex::decode(&self.salt) else {
+            return false;
+        };
+        let Ok(expected) = hex::decode(&self.hash) else {
+            return false;
+        };
+        constant_time_eq(&derive_key(pin, &salt, self.iterations), &expected)
+    }
+
+    // Checks a challenge response: hex(HMAC-SHA256(key = derived key, message = nonce)),
+    // where the client derives the key from the PIN with the salt and
+    // iteration count sent in the challenge, and the nonce is the hex string
+    // exactly as the server sent it.
+    pub fn verify_response(&self, nonce: &str, response: &str) -> bool {
+        let Ok(expected) = hex::decode(response.trim()) else {
+            return false;
+        };
+        let Ok(key) = hex::decode(&self.hash) else {
+            return false;
+        };
+        let Ok(mut mac) = HmacSha256::new_from_slice(&key) else {
+            return false;
+        };
+        mac.update(nonce.as_bytes());
+        // verify_slice compares in constant time
+        mac.verify_slice(&expected).is_ok()
+    }
+}
+
+fn derive_key(pin: &str, salt: &[u8], iterations: u32) -> [u8; HASH_LEN] {
+    let mut key = [0u8; HASH_LEN];
+    pbkdf2::pbkdf2_hmac::<Sha256>(pin.as_bytes(), salt, iterations, &mut key);
+    key
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{IpAddr, Ipv4Addr};
+
+    fn keys() -> Vec<String> {
+        limiter_keys(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 20)))
+    }
+
+    #[test]
+    fn free_attempts_then_backoff() {
+        let mut limiter = AuthLimiter::new();
+        let keys = keys();
+        for _ in 0..FREE_ATTEMPTS {
+            assert_eq!(limiter.record_failure(&keys), None);
+            assert_eq!(limiter.check(&keys), None);
+        }
+        assert_eq!(limiter.record_failure(&keys), Some(BASE_LOCKOUT));
+        assert_eq!(limiter.record_failure(&keys), Some(BASE_LOCKOUT * 2));
+        assert_eq!(limiter.record_failure(&keys), Some(BASE_LOCKOUT * 4));
+        let remaining = limiter.check(&keys).expect("not locked");
+        assert!(remaining <= BASE_LOCKOUT * 4 && remaining > BASE_LOCKOUT * 3);
+    }
+
+    #[test]
+    fn lockout_is_capped() {
+        let mut limiter = AuthLimiter::new();
+        let keys = keys();
+        let lockouts: Vec<_> = (0..40).filter_map(|_| limiter.record_failure(&keys)).collect();
+        assert!(lockouts.windows(2).all(|w| w[0] <= w[1]));
+        assert_eq!(lockouts.last(), Some(&MAX_LOCKOUT));
+        assert!(lockouts.iter().all(|&d| d <= MAX_LOCKOUT));
+    }
+
+    #[test]
+    fn success_clears_the_record() {
+        let mut limiter = AuthLimiter::new();
+        let keys = keys();
+        for _ in 0..=FREE_ATTEMPTS {
+            limiter.record_failure(&keys);
+        }
+        assert!(limiter.check(&keys).is_some());
+        limiter.record_success(&keys);
+        assert_eq!(limiter.check(&keys), None);
+        // The free attempts start over
+        assert_eq!(limiter.record_failure(&keys), None);
+    }
+
+    #[test]
+    fn attempts_are_counted_before_they_finish() {
+        let mut limiter = AuthLimiter::new();
+        let keys = keys();
+        // Parallel connections that have not finished verifying yet
+        for _ in 0..FREE_ATTEMPTS {
+            assert_eq!(limiter.begin_attempt(&keys), Ok(None));
+        }
+        assert_eq!(limiter.begin_attempt(&keys), Ok(Some(BASE_LOCKOUT)));
+        assert!(limiter.begin_attempt(&keys).is_err());
+
+        // Another address is unaffected
+        let other = limiter_keys(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 21)));
+        assert_eq!(limiter.begin_attempt(&other), Ok(None));
+        limiter.record_success(&other);
+        assert_eq!(limiter.check(&other), None);
+    }
+
+    #[test]
+    fn pin_hash_verifies() {
+        let hash = PinHash::with_salt("482913", &[7; SALT_LEN], 10);
+        assert!(hash.verify("482913"));
+        assert!(!hash.verify("482914"));
+
+        let nonce = "00ff";
+        let mut mac = HmacSha256::new_from_slice(&hex::decode(&hash.hash).unwrap()).unwrap();
+        mac.update(nonce.as_bytes());
+        let response = hex::encode(mac.finalize().into_bytes());
+        assert!(hash.verify_response(nonce, &response));
+        assert!(!hash.verify_response("00fe", &response));
+        assert!(!hash.verify_response(nonce, "not hex"));
+    }
+}
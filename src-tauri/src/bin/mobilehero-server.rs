@@ -0,0 +1,63 @@
+// Headless server: the launcher's WebSocket server and keyboard output
+// without the GUI, for machines with no desktop session
+use clap::Parser;
+use std::path::PathBuf;
+
+use mobileherolauncher_lib::headless::{self, HeadlessOptions, LogLevel};
+
+#[derive(Parser)]
+#[command(name = "mobilehero-server", version, about = "MobileHero controller server without the GUI")]
+struct Args {
+    /// Port to listen on [default: from the config file]
+    #[arg(short, long)]
+    port: Option<u16>,
+
+    /// PIN for this session instead of the configured one. Prefer the
+    /// environment variable or --pin-stdin: arguments show up in `ps` and
+    /// shell history
+    #[arg(long, env = "MOBILEHERO_PIN", hide_env_values = true)]
+    pin: Option<String>,
+
+    /// Read the session PIN from the first line of stdin
+    #[arg(long, conflicts_with = "pin")]
+    pin_stdin: bool,
+
+    /// Config file [default: the launcher's config]
+    #[arg(short, long)]
+    config: Option<PathBuf>,
+
+    /// "all", "loopback" or an interface address [default: from the config file]
+    #[arg(short, long)]
+    bind: Option<String>,
+
+    /// error, warn, info or debug (debug logs every button press)
+    #[arg(long, default_value = "info")]
+    log_level: LogLevel,
+}
+
+#[tokio::main]
+async fn main() {
+    let args = Args::parse();
+    let pin = if args.pin_stdin {
+        let mut line = String::new();
+        if let Err(e) = std::io::stdin().read_line(&mut line) {
+            eprintln!("Could not read the PIN from stdin: {}", e);
+            std::process::exit(1);
+        }
+        Some(line.trim().to_string())
+    } else {
+        args.pin
+    };
+    let options = HeadlessOptions {
+        config_path: args.config,
+        port: args.port,
+        bind_address: args.bind,
+        pin,
+        log_level: args.log_level,
+    };
+
+    if let Err(e) = headless::run(options).await {
+        eprintln!("Server error: {}", e);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,203 @@
+use serde::Serialize;
+
+use crate::config::Config;
+
+// Every control a client can send, resolved from its message once so the
+// input path never builds or compares binding names
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Green,
+    Red,
+    Yellow,
+    Blue,
+    Orange,
+    StrumUp,
+    StrumDown,
+    Starpower,
+    Whammy,
+    Tilt,
+    Start,
+    Select,
+    Left,
+    Right,
+    Up,
+    Down,
+    DrumRed,
+    DrumYellow,
+    DrumBlue,
+    DrumOrange,
+    DrumGreen,
+    DrumKick,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ActionGroup {
+    Fret,
+    Strum,
+    Action,
+    Nav,
+    Drum,
+}
+
+impl Action {
+    pub const COUNT: usize = 22;
+
+    pub const ALL: [Action; Action::COUNT] = [
+        Action::Green,
+        Action::Red,
+        Action::Yellow,
+        Action::Blue,
+        Action::Orange,
+        Action::StrumUp,
+        Action::StrumDown,
+        Action::Starpower,
+        Action::Whammy,
+        Action::Tilt,
+        Action::Start,
+        Action::Select,
+        Action::Left,
+        Action::Right,
+        Action::Up,
+        Action::Down,
+        Action::DrumRed,
+        Action::DrumYellow,
+        Action::DrumBlue,
+        Action::DrumOrange,
+        Action::DrumGreen,
+        Action::DrumKick,
+    ];
+
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    // Key in `Config.key_bindings`
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Green => "green",
+            Action::Red => "red",
+            Action::Yellow => "yellow",
+            Action::Blue => "blue",
+            Action::Orange => "orange",
+            Action::StrumUp => "strum_up",
+            Action::StrumDown => "strum_down",
+            Action::Starpower => "starpower",
+            Action::Whammy => "whammy",
+            Action::Tilt => "tilt",
+            Action::Start => "start",
+            Action::Select => "select",
+            Action::Left => "left",
+            Action::Right => "right",
+            Action::Up => "up",
+            Action::Down => "down",
+            Action::DrumRed => "drum_red",
+            Action::DrumYellow => "drum_yellow",
+            Action::DrumBlue => "drum_blue",
+            Action::DrumOrange => "drum_orange",
+            Action::DrumGreen => "drum_green",
+            Action::DrumKick => "drum_kick",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Action> {
+        Action::ALL.iter().copied().find(|action| action.name() == name)
+    }
+
+    pub fn group(self) -> ActionGroup {
+        match self {
+            Action::Green | Action::Red | Action::Yellow | Action::Blue | Action::Orange => ActionGroup::Fret,
+            Action::StrumUp | Action::StrumDown => ActionGroup::Strum,
+            Action::Starpower | Action::Whammy | Action::Tilt | Action::Start | Action::Select => ActionGroup::Action,
+            Action::Left | Action::Right | Action::Up | Action::Down => ActionGroup::Nav,
+            Action::DrumRed
+            | Action::DrumYellow
+            | Action::DrumBlue
+            | Action::DrumOrange
+            | Action::DrumGreen
+            | Action::DrumKick => ActionGroup::Drum,
+        }
+    }
+
+    // The name without its group prefix, as clients send it ("up" for
+    // strum_up, "kick" for drum_kick)
+    pub fn short_name(self) -> &'static str {
+        let name = self.name();
+        name.strip_prefix("strum_")
+            .or_else(|| name.strip_prefix("drum_"))
+            .unwrap_or(name)
+    }
+
+    // Maps a client message to its action, e.g. ("fret", "green"),
+    // ("strum", "up"), ("drum", "kick") or ("start", _)
+    pub fn from_message(msg_type: &str, value: Option<&str>) -> Option<Action> {
+        let action = match (msg_type, value) {
+            ("fret", Some("green")) => Action::Green,
+            ("fret", Some("red")) => Action::Red,
+            ("fret", Some("yellow")) => Action::Yellow,
+            ("fret", Some("blue")) => Action::Blue,
+            ("fret", Some("orange")) => Action::Orange,
+            ("strum", Some("up")) => Action::StrumUp,
+            ("strum", Some("down")) => Action::StrumDown,
+            ("drum", Some("red")) => Action::DrumRed,
+            ("drum", Some("yellow")) => Action::DrumYellow,
+            ("drum", Some("blue")) => Action::DrumBlue,
+            ("drum", Some("orange")) => Action::DrumOrange,
+            ("drum", Some("green")) => Action::DrumGreen,
+            ("drum", Some("kick")) => Action::DrumKick,
+            ("starpower", _) => Action::Starpower,
+            ("whammy", _) => Action::Whammy,
+            ("start", _) => Action::Start,
+            ("select", _) => Action::Select,
+            ("left", _) => Action::Left,
+            ("right", _) => Action::Right,
+            ("up", _) => Action::Up,
+            ("down", _) => Action::Down,
+            _ => return None,
+        };
+        Some(action)
+    }
+
+    // Controls clients may send as analog "axis" messages
+    pub fn from_axis(name: &str) -> Option<Action> {
+        match name {
+            "whammy" => Some(Action::Whammy),
+            "tilt" => Some(Action::Tilt),
+            _ => None,
+        }
+    }
+}
+
+// Bindings resolved once per config load: one slot per action, indexed by
+// `Action::index`. `K` is whatever the backend sends (an enigo key, ...).
+#[derive(Debug, Clone)]
+pub struct BindingTable<K> {
+    slots: Vec<Option<K>>,
+}
+
+impl<K> BindingTable<K> {
+    // Resolves every binding with `parse`. Bindings it rejects are returned
+    // as "action: binding" so they can be reported up front.
+    pub fn resolve<F>(config: &Config, mut parse: F) -> (Self, Vec<String>)
+    where
+        F: FnMut(&str) -> Option<K>,
+    {
+        let mut errors = Vec::new();
+        let slots = Action::ALL
+            .iter()
+            .map(|action| {
+                let binding = config.key_bindings.get(action.name())?;
+                let key = parse(binding);
+                if key.is_none() {
+                    errors.push(format!("{}: {}", action.name(), binding));
+                }
+                key
+            })
+            .collect();
+        (Self { slots }, errors)
+    }
+
+    pub fn get(&self, action: Action) -> Option<&K> {
+        self.slots[action.index()].as_ref()
+    }
+}
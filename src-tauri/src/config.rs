@@ -1,13 +1,83 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+
+use crate::auth::PinHash;
+use crate::discovery;
+use crate::macros::MacroStep;
+use crate::netfilter;
+use crate::output::OutputBackendKind;
+use crate::repeat::AutoRepeatConfig;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
+    // Name shown to phones browsing for PCs; empty uses the hostname
+    #[serde(default)]
+    pub server_name: String,
     pub port: u16,
+    // "all", "loopback" or a specific interface address
+    #[serde(default = "default_bind_address")]
+    pub bind_address: String,
+    // Bind the IPv6 wildcard and accept IPv4 clients on the same socket
+    #[serde(default)]
+    pub dual_stack: bool,
+    // Plaintext PIN. Only set transiently (the defaults, config files from
+    // older versions, or a new PIN typed into the settings UI) and hashed
+    // into `pin_hash` before anything is written to disk.
+    #[serde(default)]
     pub pin: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pin_hash: Option<PinHash>,
     pub key_bindings: HashMap<String, String>,
+    // Accept `{"type":"auth","pin":"..."}` from clients that predate the
+    // challenge-response handshake. The PIN then travels in cleartext.
+    // Off for new installs; turned on once for config files written before
+    // the handshake existed, whose phones may still send the plain PIN.
+    #[serde(default)]
+    pub legacy_pin_auth: bool,
+    // Generate a fresh random PIN every time the server starts
+    #[serde(default)]
+    pub randomize_pin: bool,
+    // CIDR ranges allowed to connect (empty allows everyone) and ranges that
+    // are always rejected. Checked before the WebSocket handshake.
+    #[serde(default = "netfilter::default_allowed_networks")]
+    pub allowed_networks: Vec<String>,
+    #[serde(default)]
+    pub denied_networks: Vec<String>,
+    // Advertise the server as `_mobilehero._tcp` over mDNS while it runs
+    #[serde(default = "default_mdns_enabled")]
+    pub mdns_enabled: bool,
+    // Answer UDP broadcast probes, for networks that filter multicast
+    #[serde(default)]
+    pub udp_discovery: bool,
+    #[serde(default = "default_discovery_port")]
+    pub discovery_port: u16,
+    // What controller input is turned into
+    #[serde(default)]
+    pub output_backend: OutputBackendKind,
+    // Named step sequences clients can trigger with {"type":"macro"}
+    #[serde(default)]
+    pub macros: HashMap<String, Vec<MacroStep>>,
+    // Per-action limit (ms) after which a held control is released as
+    // stuck; 0 disables it. Unlisted actions use the watchdog defaults.
+    #[serde(default)]
+    pub max_hold_ms: HashMap<String, u64>,
+    // Repeats held up/down/left/right like a keyboard does
+    #[serde(default)]
+    pub auto_repeat: AutoRepeatConfig,
+}
+
+fn default_bind_address() -> String {
+    "all".to_string()
+}
+
+fn default_mdns_enabled() -> bool {
+    true
+}
+
+fn default_discovery_port() -> u16 {
+    discovery::DEFAULT_DISCOVERY_PORT
 }
 
 impl Default for Config {
@@ -46,10 +116,57 @@ impl Default for Config {
         key_bindings.insert("drum_kick".to_string(), "Space".to_string());
 
         Self {
+            server_name: String::new(),
             port: 8080,
+            bind_address: default_bind_address(),
+            dual_stack: false,
             pin: "1234".to_string(),
+            pin_hash: None,
             key_bindings,
+            legacy_pin_auth: false,
+            randomize_pin: false,
+            allowed_networks: netfilter::default_allowed_networks(),
+            denied_networks: Vec::new(),
+            mdns_enabled: default_mdns_enabled(),
+            udp_discovery: false,
+            discovery_port: default_discovery_port(),
+            output_backend: OutputBackendKind::default(),
+            macros: HashMap::new(),
+            max_hold_ms: HashMap::new(),
+            auto_repeat: AutoRepeatConfig::default(),
+        }
+    }
+}
+
+impl Config {
+    // Moves a plaintext PIN into `pin_hash`. Returns true if anything changed.
+    pub fn hash_pin(&mut self) -> bool {
+        if self.pin.is_empty() {
+            return false;
+        }
+        self.pin_hash = Some(PinHash::new(&self.pin));
+        self.pin.clear();
+        true
+    }
+
+    // Friendly name for discovery: the configured one, else the hostname
+    pub fn display_name(&self) -> String {
+        if !self.server_name.trim().is_empty() {
+            return self.server_name.trim().to_string();
         }
+        hostname::get()
+            .ok()
+            .and_then(|h| h.into_string().ok())
+            .filter(|h| !h.is_empty())
+            .unwrap_or_else(|| "MobileHero PC".to_string())
+    }
+
+    // Copy that is safe to hand to the webview: neither the PIN nor its hash
+    pub fn redacted(&self) -> Config {
+        let mut config = self.clone();
+        config.pin.clear();
+        config.pin_hash = None;
+        config
     }
 }
 
@@ -61,11 +178,27 @@ pub fn load_config(path: Option<PathBuf>) -> Config {
     // Actually, for now let's say if path is None we return default, or we can try to resolve it here using 'directories' crate if we add it.
     // But since we want to use Tauri's path resolver, let's assume the caller ALWAYS handles the path resolution.
 
-    if let Some(p) = path {
+    let mut loaded = None;
+    let mut migrated = false;
+    if let Some(p) = &path {
         if p.exists() {
-            match fs::read_to_string(&p) {
-                Ok(contents) => match serde_json::from_str(&contents) {
-                    Ok(config) => return config,
+            match fs::read_to_string(p) {
+                Ok(contents) => match serde_json::from_str::<serde_json::Value>(&contents) {
+                    Ok(value) => {
+                        // Files without the switch predate the challenge
+                        // handshake, so keep their phones working
+                        let predates_handshake = value.get("legacy_pin_auth").is_none();
+                        match serde_json::from_value::<Config>(value) {
+                            Ok(mut config) => {
+                                if predates_handshake {
+                                    config.legacy_pin_auth = true;
+                                    migrated = true;
+                                }
+                                loaded = Some(config);
+                            }
+                            Err(e) => eprintln!("Failed to parse config: {}", e),
+                        }
+                    }
                     Err(e) => eprintln!("Failed to parse config: {}", e),
                 },
                 Err(e) => eprintln!("Failed to read config: {}", e),
@@ -73,7 +206,19 @@ pub fn load_config(path: Option<PathBuf>) -> Config {
         }
     }
 
-    Config::default()
+    let mut config: Config = loaded.unwrap_or_default();
+
+    // Migrate plaintext PINs (older config files, or the defaults on first
+    // run) to a salted hash and persist it so this only happens once
+    if config.hash_pin() || migrated {
+        if let Some(p) = path {
+            if let Err(e) = save_config(&config, p) {
+                eprintln!("Failed to save migrated config: {}", e);
+            }
+        }
+    }
+
+    config
 }
 
 pub fn save_config(config: &Config, path: PathBuf) -> Result<(), Box<dyn std::error::Error>> {
@@ -81,7 +226,34 @@ pub fn save_config(config: &Config, path: PathBuf) -> Result<(), Box<dyn std::er
     if let Some(parent) = path.parent() {
         fs::create_dir_all(parent)?;
     }
-    let json = serde_json::to_string_pretty(config)?;
-    fs::write(path, json)?;
+    // Never write a plaintext PIN
+    let mut config = config.clone();
+    config.hash_pin();
+    let json = serde_json::to_string_pretty(&config)?;
+    write_private(&path, json.as_bytes())?;
     Ok(())
 }
+
+// The PIN hash logs in by itself (see `PinHash`), so only the owner may read
+// the file. On Windows the per-user config directory already has an
+// owner-only ACL.
+#[cfg(unix)]
+fn write_private(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+    use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)?;
+    // `mode` only applies to new files; tighten ones written by older versions
+    file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    file.write_all(contents)
+}
+
+#[cfg(not(unix))]
+fn write_private(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    fs::write(path, contents)
+}
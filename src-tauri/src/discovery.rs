@@ -0,0 +1,266 @@
+use mdns_sd::{ServiceDaemon, ServiceInfo};
+use serde::{Deserialize, Serialize};
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use tokio::net::UdpSocket;
+
+use crate::config::Config;
+use crate::events::EventSink;
+use crate::netfilter::NetworkFilter;
+use crate::network::BindTarget;
+use crate::websocket::PROTOCOL_VERSION;
+
+pub const MDNS_SERVICE_TYPE: &str = "_mobilehero._tcp.local.";
+pub const DEFAULT_DISCOVERY_PORT: u16 = 8089;
+
+// Advertises the running server over mDNS / DNS-SD so the app can list
+// nearby PCs without scanning a QR code. Withdrawn by `stop`.
+pub struct MdnsAdvertiser {
+    daemon: ServiceDaemon,
+    fullname: String,
+}
+
+impl MdnsAdvertiser {
+    pub fn start(config: &Config, bind: BindTarget) -> Result<Self, String> {
+        let name = config.display_name();
+        let host = format!("{}.local.", mdns_host_label(&name));
+        let port = config.port.to_string();
+        let version = PROTOCOL_VERSION.to_string();
+        // No TLS yet, so there is no certificate fingerprint to publish
+        let properties = [
+            ("name", name.as_str()),
+            ("port", port.as_str()),
+            ("v", version.as_str()),
+        ];
+
+        let info = match bind {
+            BindTarget::Loopback => {
+                return Err("mDNS is not advertised in loopback-only mode".to_string())
+            }
+            BindTarget::Address(ip) if !ip.is_unspecified() => ServiceInfo::new(
+                MDNS_SERVICE_TYPE,
+                &name,
+                &host,
+                ip,
+                config.port,
+                &properties[..],
+            ),
+            _ => ServiceInfo::new(MDNS_SERVICE_TYPE, &name, &host, (), config.port, &properties[..])
+                .map(|info| info.enable_addr_auto()),
+        }
+        .map_err(|e| e.to_string())?;
+
+        let daemon = ServiceDaemon::new().map_err(|e| e.to_string())?;
+        let fullname = info.get_fullname().to_string();
+        daemon.register(info).map_err(|e| e.to_string())?;
+
+        Ok(Self { daemon, fullname })
+    }
+
+    pub fn stop(self) {
+        // Sends goodbye packets so browsers drop the entry right away
+        if let Ok(receiver) = self.daemon.unregister(&self.fullname) {
+            let _ = receiver.recv_timeout(std::time::Duration::from_secs(1));
+        }
+        let _ = self.daemon.shutdown();
+    }
+}
+
+// DNS host labels only allow letters, digits and hyphens
+fn mdns_host_label(name: &str) -> String {
+    let label: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect();
+    let label = label.trim_matches('-');
+    if label.is_empty() {
+        "mobilehero".to_string()
+    } else {
+        label.to_string()
+    }
+}
+
+// UDP discovery, for networks where multicast (and so mDNS) is filtered.
+// The phone broadcasts `{"type":"discover"}` to the discovery port and every
+// running server answers it directly with a `DiscoveryReply`.
+#[derive(Debug, Deserialize)]
+struct DiscoveryProbe {
+    #[serde(rename = "type")]
+    msg_type: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiscoveryReply {
+    #[serde(rename = "type")]
+    pub msg_type: String,
+    pub name: String,
+    pub addresses: Vec<String>,
+    pub port: u16,
+    pub v: u32,
+}
+
+impl DiscoveryReply {
+    pub fn new(config: &Config, bind: BindTarget) -> Self {
+        Self {
+            msg_type: "discover_reply".to_string(),
+            name: config.display_name(),
+            addresses: bind
+                .pairing_hosts(config.dual_stack)
+                .iter()
+                .map(|ip| ip.to_string())
+                .collect(),
+            port: config.port,
+            v: PROTOCOL_VERSION,
+        }
+    }
+}
+
+// Socket the responder listens on. Loopback-only servers answer loopback
+// probes only; otherwise probes are accepted on every interface.
+pub async fn bind_discovery_socket(bind: BindTarget, port: u16) -> std::io::Result<UdpSocket> {
+    let ip = match bind {
+        BindTarget::Loopback => Ipv4Addr::LOCALHOST,
+        _ => Ipv4Addr::UNSPECIFIED,
+    };
+    UdpSocket::bind(SocketAddr::from((ip, port))).await
+}
+
+// Answers probes until the task is dropped or aborted. Takes an already bound
+// socket so it can run against 127.0.0.1:0 just as well as the real port.
+// Probes from addresses the network filter rejects go unanswered, so a
+// denied host cannot learn the server exists.
+pub async fn run_udp_responder(
+    socket: UdpSocket,
+    reply: DiscoveryReply,
+    filter: NetworkFilter,
+    sink: Arc<dyn EventSink>,
+) -> std::io::Result<()> {
+    let reply = serde_json::to_vec(&reply).map_err(std::io::Error::other)?;
+    let mut buf = [0u8; 512];
+
+    loop {
+        let (len, from) = match socket.recv_from(&mut buf).await {
+            Ok(received) => received,
+            Err(e) if is_transient(&e) => {
+                sink.log(format!("[WARN] Discovery receive failed, still listening: {}", e));
+                continue;
+            }
+            Err(e) => {
+                sink.log(format!("[WARN] UDP discovery stopped: {}", e));
+                return Err(e);
+            }
+        };
+        if !filter.permits(from.ip()) {
+            continue;
+        }
+        let is_probe = serde_json::from_slice::<DiscoveryProbe>(&buf[..len])
+            .map(|probe| probe.msg_type == "discover")
+            .unwrap_or(false);
+
+        if is_probe {
+            if let Err(e) = socket.send_to(&reply, from).await {
+                sink.log(format!("[WARN] Discovery reply to {} failed: {}", from, e));
+            }
+        }
+    }
+}
+
+// Errors about one datagram or peer rather than the socket. Windows reports
+// an ICMP port unreachable caused by an earlier reply as WSAECONNRESET on the
+// next receive.
+fn is_transient(e: &std::io::Error) -> bool {
+    use std::io::ErrorKind;
+    matches!(
+        e.kind(),
+        ErrorKind::ConnectionReset
+            | ErrorKind::ConnectionRefused
+            | ErrorKind::ConnectionAborted
+            | ErrorKind::Interrupted
+            | ErrorKind::WouldBlock
+            | ErrorKind::TimedOut
+            | ErrorKind::InvalidData
+    ) || (cfg!(windows) && e.raw_os_error() == Some(WSAEMSGSIZE))
+}
+
+// Datagram larger than the buffer; Windows reports it as an error and
+// drops the rest
+const WSAEMSGSIZE: i32 = 10040;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::NullSink;
+    use std::time::Duration;
+
+    fn reply() -> DiscoveryReply {
+        DiscoveryReply {
+            msg_type: "discover_reply".to_string(),
+            name: "Test PC".to_string(),
+            addresses: vec!["127.0.0.1".to_string()],
+            port: 8080,
+            v: PROTOCOL_VERSION,
+        }
+    }
+
+    // Starts a responder on an ephemeral loopback port and sends it one datagram
+    async fn probe(filter: NetworkFilter, message: &[u8]) -> Option<DiscoveryReply> {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let target = socket.local_addr().unwrap();
+        let responder = tokio::spawn(run_udp_responder(socket, reply(), filter, Arc::new(NullSink)));
+
+        let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        client.send_to(message, target).await.unwrap();
+        let mut buf = [0u8; 512];
+        let received = tokio::time::timeout(Duration::from_millis(500), client.recv_from(&mut buf)).await;
+        responder.abort();
+
+        let (len, _) = received.ok()?.unwrap();
+        Some(serde_json::from_slice(&buf[..len]).unwrap())
+    }
+
+    fn filter(allowed: &[&str], denied: &[&str]) -> NetworkFilter {
+        let config = Config {
+            allowed_networks: allowed.iter().map(|s| s.to_string()).collect(),
+            denied_networks: denied.iter().map(|s| s.to_string()).collect(),
+            ..Default::default()
+        };
+        NetworkFilter::from_config(&config).0
+    }
+
+    #[test]
+    fn per_datagram_errors_are_transient() {
+        use std::io::{Error, ErrorKind};
+        assert!(is_transient(&Error::from(ErrorKind::ConnectionReset)));
+        assert!(is_transient(&Error::from(ErrorKind::ConnectionRefused)));
+        assert!(is_transient(&Error::from(ErrorKind::Interrupted)));
+        assert_eq!(is_transient(&Error::from_raw_os_error(WSAEMSGSIZE)), cfg!(windows));
+        assert!(!is_transient(&Error::from(ErrorKind::PermissionDenied)));
+        assert!(!is_transient(&Error::from(ErrorKind::NotConnected)));
+    }
+
+    #[tokio::test]
+    async fn answers_discover_probe() {
+        let answer = probe(filter(&["127.0.0.0/8"], &[]), br#"{"type":"discover"}"#)
+            .await
+            .expect("no reply");
+        assert_eq!(answer.msg_type, "discover_reply");
+        assert_eq!(answer.name, "Test PC");
+        assert_eq!(answer.addresses, ["127.0.0.1"]);
+        assert_eq!(answer.port, 8080);
+        assert_eq!(answer.v, PROTOCOL_VERSION);
+    }
+
+    #[tokio::test]
+    async fn ignores_other_messages() {
+        assert!(probe(filter(&[], &[]), br#"{"type":"hello"}"#).await.is_none());
+        assert!(probe(filter(&[], &[]), b"not json").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn ignores_filtered_senders() {
+        let denied = filter(&[], &["127.0.0.0/8"]);
+        assert!(probe(denied, br#"{"type":"discover"}"#).await.is_none());
+        let not_allowed = filter(&["192.168.0.0/16"], &[]);
+        assert!(probe(not_allowed, br#"{"type":"discover"}"#).await.is_none());
+    }
+}
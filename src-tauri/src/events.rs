@@ -0,0 +1,91 @@
+use std::net::SocketAddr;
+use std::sync::Mutex;
+
+use crate::bindings::Action;
+
+// Everything the server core reports while it runs
+#[derive(Debug, Clone, PartialEq)]
+pub enum ServerEvent {
+    // Human readable line; the severity is carried by a "[TAG]" prefix
+    Log(String),
+    Status(ServerStatus),
+    ClientCount(u32),
+    ClientAuthenticated(SocketAddr),
+    ClientDisconnected(SocketAddr),
+    // The watchdog let go of a control the client never released
+    ForcedRelease(SocketAddr, Action),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ServerStatus {
+    Running,
+    Stopped,
+}
+
+impl ServerStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ServerStatus::Running => "running",
+            ServerStatus::Stopped => "stopped",
+        }
+    }
+}
+
+// Where the server sends its events. The launcher forwards them to the
+// webview, the headless server prints them, tests record them.
+pub trait EventSink: Send + Sync {
+    fn emit(&self, event: ServerEvent);
+
+    fn log(&self, line: String) {
+        self.emit(ServerEvent::Log(line));
+    }
+
+    // Whether to send a line for every button press ("[FRET] green pressed",
+    // ...). The server skips formatting them when this is false.
+    fn wants_input_logs(&self) -> bool {
+        true
+    }
+}
+
+// Drops everything
+pub struct NullSink;
+
+impl EventSink for NullSink {
+    fn emit(&self, _event: ServerEvent) {}
+
+    fn wants_input_logs(&self) -> bool {
+        false
+    }
+}
+
+// Keeps every event in order, for tests and embedders that poll
+#[derive(Default)]
+pub struct RecordingSink {
+    events: Mutex<Vec<ServerEvent>>,
+}
+
+impl RecordingSink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn events(&self) -> Vec<ServerEvent> {
+        self.events.lock().unwrap().clone()
+    }
+
+    pub fn logs(&self) -> Vec<String> {
+        self.events()
+            .into_iter()
+            .filter_map(|event| match event {
+                ServerEvent::Log(line) => Some(line),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+impl EventSink for RecordingSink {
+    fn emit(&self, event: ServerEvent) {
+        self.events.lock().unwrap().push(event);
+    }
+}
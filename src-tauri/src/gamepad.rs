@@ -0,0 +1,596 @@
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::sync::{Arc, Mutex};
+
+use crate::bindings::Action;
+use crate::output::OutputBackend;
+
+// Linux input event types and codes (linux/input-event-codes.h)
+pub const EV_SYN: u16 = 0x00;
+pub const EV_KEY: u16 = 0x01;
+pub const EV_ABS: u16 = 0x03;
+pub const SYN_REPORT: u16 = 0x00;
+
+pub const BTN_SOUTH: u16 = 0x130;
+pub const BTN_EAST: u16 = 0x131;
+pub const BTN_NORTH: u16 = 0x133;
+pub const BTN_WEST: u16 = 0x134;
+pub const BTN_TL: u16 = 0x136;
+pub const BTN_TR: u16 = 0x137;
+pub const BTN_SELECT: u16 = 0x13a;
+pub const BTN_START: u16 = 0x13b;
+pub const BTN_THUMBL: u16 = 0x13d;
+pub const BTN_DPAD_UP: u16 = 0x220;
+pub const BTN_DPAD_DOWN: u16 = 0x221;
+pub const BTN_DPAD_LEFT: u16 = 0x222;
+pub const BTN_DPAD_RIGHT: u16 = 0x223;
+
+pub const ABS_RX: u16 = 0x03;
+pub const ABS_RY: u16 = 0x04;
+
+const AXIS_MIN: i32 = -32768;
+const AXIS_MAX: i32 = 32767;
+
+// pid.codes test VID/PID; games see an unknown gamepad and let the player
+// bind its buttons
+const VENDOR_ID: u16 = 0x1209;
+const PRODUCT_ID: u16 = 0x0001;
+
+enum Control {
+    Button(u16),
+    Axis(u16),
+}
+
+// Laid out like an Xbox guitar controller, which Clone Hero and YARG know:
+// frets on the face buttons and LB, strum on the d-pad, whammy and tilt on
+// the right stick. Drums share the same device: a player plays one
+// instrument at a time, so the pads reuse the face buttons and games map
+// them in their drum profile.
+fn control(action: Action) -> Control {
+    match action {
+        Action::Green | Action::DrumGreen => Control::Button(BTN_SOUTH),
+        Action::Red | Action::DrumRed => Control::Button(BTN_EAST),
+        Action::Yellow | Action::DrumYellow => Control::Button(BTN_NORTH),
+        Action::Blue | Action::DrumBlue => Control::Button(BTN_WEST),
+        Action::Orange | Action::DrumOrange => Control::Button(BTN_TL),
+        Action::DrumKick => Control::Button(BTN_THUMBL),
+        Action::Starpower => Control::Button(BTN_TR),
+        Action::Start => Control::Button(BTN_START),
+        Action::Select => Control::Button(BTN_SELECT),
+        Action::StrumUp | Action::Up => Control::Button(BTN_DPAD_UP),
+        Action::StrumDown | Action::Down => Control::Button(BTN_DPAD_DOWN),
+        Action::Left => Control::Button(BTN_DPAD_LEFT),
+        Action::Right => Control::Button(BTN_DPAD_RIGHT),
+        Action::Whammy => Control::Axis(ABS_RX),
+        Action::Tilt => Control::Axis(ABS_RY),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AxisSpec {
+    pub code: u16,
+    pub min: i32,
+    pub max: i32,
+}
+
+// Everything needed to create one virtual device
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeviceSpec {
+    pub name: String,
+    pub vendor: u16,
+    pub product: u16,
+    pub keys: Vec<u16>,
+    pub axes: Vec<AxisSpec>,
+}
+
+impl DeviceSpec {
+    fn guitar(player: u8) -> Self {
+        Self {
+            name: format!("MobileHero Guitar (Player {})", player + 1),
+            vendor: VENDOR_ID,
+            product: PRODUCT_ID,
+            keys: vec![
+                BTN_SOUTH, BTN_EAST, BTN_NORTH, BTN_WEST, BTN_TL, BTN_TR, BTN_SELECT, BTN_START,
+                BTN_THUMBL, BTN_DPAD_UP, BTN_DPAD_DOWN, BTN_DPAD_LEFT, BTN_DPAD_RIGHT,
+            ],
+            axes: [ABS_RX, ABS_RY]
+                .iter()
+                .map(|&code| AxisSpec { code, min: AXIS_MIN, max: AXIS_MAX })
+                .collect(),
+        }
+    }
+}
+
+// A created device. Events are buffered by the kernel until a SYN_REPORT.
+pub trait EventWriter: Send {
+    fn write_event(&mut self, kind: u16, code: u16, value: i32) -> io::Result<()>;
+}
+
+// Creates devices: /dev/uinput for real, `MemoryDevices` in tests
+pub trait DeviceFactory: Send {
+    fn create(&mut self, spec: &DeviceSpec) -> io::Result<Box<dyn EventWriter>>;
+}
+
+struct Pad {
+    writer: Box<dyn EventWriter>,
+    // Actions held down, and how many of them hold each button. Actions
+    // share buttons (strum up and nav up, frets and drum pads), so a button
+    // only goes up once nothing holds it.
+    held: HashSet<Action>,
+    pressed: HashMap<u16, u32>,
+}
+
+impl Pad {
+    fn send(&mut self, kind: u16, code: u16, value: i32) -> io::Result<()> {
+        self.writer.write_event(kind, code, value)?;
+        self.writer.write_event(EV_SYN, SYN_REPORT, 0)
+    }
+}
+
+// One virtual guitar per player slot, created when the player connects and
+// kept until the server stops so games don't reshuffle players
+pub struct VirtualGamepads {
+    factory: Box<dyn DeviceFactory>,
+    pads: HashMap<u8, Pad>,
+}
+
+impl VirtualGamepads {
+    pub fn new(factory: Box<dyn DeviceFactory>) -> Self {
+        Self {
+            factory,
+            pads: HashMap::new(),
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    pub fn uinput() -> Result<Self, String> {
+        uinput::check_access().map_err(|e| format!("Cannot open /dev/uinput: {}", e))?;
+        Ok(Self::new(Box::new(uinput::Uinput)))
+    }
+
+    fn pad(&mut self, player: u8) -> Result<&mut Pad, String> {
+        if !self.pads.contains_key(&player) {
+            let writer = self
+                .factory
+                .create(&DeviceSpec::guitar(player))
+                .map_err(|e| format!("Failed to create gamepad for player {}: {}", player + 1, e))?;
+            self.pads.insert(
+                player,
+                Pad {
+                    writer,
+                    held: HashSet::new(),
+                    pressed: HashMap::new(),
+                },
+            );
+        }
+        Ok(self.pads.get_mut(&player).unwrap())
+    }
+
+    fn button(&mut self, player: u8, action: Action, down: bool) -> Result<(), String> {
+        let Control::Button(code) = control(action) else {
+            return Ok(());
+        };
+        let pad = self.pad(player)?;
+        // Repeated presses and releases of the same action change nothing
+        if down == pad.held.contains(&action) {
+            return Ok(());
+        }
+        if down {
+            pad.held.insert(action);
+        } else {
+            pad.held.remove(&action);
+        }
+        let count = pad.pressed.entry(code).or_insert(0);
+        let changed = if down {
+            *count += 1;
+            *count == 1
+        } else {
+            *count -= 1;
+            *count == 0
+        };
+        if *count == 0 {
+            pad.pressed.remove(&code);
+        }
+        if !changed {
+            return Ok(());
+        }
+        pad.send(EV_KEY, code, down as i32)
+            .map_err(|e| format!("Gamepad write failed: {}", e))
+    }
+}
+
+impl OutputBackend for VirtualGamepads {
+    fn connect(&mut self, player: u8) -> Result<(), String> {
+        self.pad(player).map(|_| ())
+    }
+
+    fn press(&mut self, player: u8, action: Action) -> Result<(), String> {
+        self.button(player, action, true)
+    }
+
+    fn release(&mut self, player: u8, action: Action) -> Result<(), String> {
+        self.button(player, action, false)
+    }
+
+    fn axis(&mut self, player: u8, action: Action, value: f32) -> Result<(), String> {
+        let Control::Axis(code) = control(action) else {
+            return Ok(());
+        };
+        let scaled = (value.clamp(-1.0, 1.0) * AXIS_MAX as f32) as i32;
+        self.pad(player)?
+            .send(EV_ABS, code, scaled)
+            .map_err(|e| format!("Gamepad write failed: {}", e))
+    }
+
+    fn release_all(&mut self, player: u8) {
+        let Some(pad) = self.pads.get_mut(&player) else {
+            return;
+        };
+        pad.held.clear();
+        for (code, _) in std::mem::take(&mut pad.pressed) {
+            let _ = pad.writer.write_event(EV_KEY, code, 0);
+        }
+        for code in [ABS_RX, ABS_RY] {
+            let _ = pad.writer.write_event(EV_ABS, code, 0);
+        }
+        let _ = pad.writer.write_event(EV_SYN, SYN_REPORT, 0);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemoryEvent {
+    // Index into `MemoryDevices::devices`
+    pub device: usize,
+    pub kind: u16,
+    pub code: u16,
+    pub value: i32,
+}
+
+// Fake factory keeping created devices and written events in memory. Clones
+// share the same log.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryDevices {
+    devices: Arc<Mutex<Vec<DeviceSpec>>>,
+    events: Arc<Mutex<Vec<MemoryEvent>>>,
+}
+
+impl MemoryDevices {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn devices(&self) -> Vec<DeviceSpec> {
+        self.devices.lock().unwrap().clone()
+    }
+
+    pub fn events(&self) -> Vec<MemoryEvent> {
+        self.events.lock().unwrap().clone()
+    }
+}
+
+impl DeviceFactory for MemoryDevices {
+    fn create(&mut self, spec: &DeviceSpec) -> io::Result<Box<dyn EventWriter>> {
+        let mut devices = self.devices.lock().unwrap();
+        devices.push(spec.clone());
+        Ok(Box::new(MemoryWriter {
+            device: devices.len() - 1,
+            events: Arc::clone(&self.events),
+        }))
+    }
+}
+
+struct MemoryWriter {
+    device: usize,
+    events: Arc<Mutex<Vec<MemoryEvent>>>,
+}
+
+impl EventWriter for MemoryWriter {
+    fn write_event(&mut self, kind: u16, code: u16, value: i32) -> io::Result<()> {
+        self.events.lock().unwrap().push(MemoryEvent {
+            device: self.device,
+            kind,
+            code,
+            value,
+        });
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod uinput {
+    use std::fs::{File, OpenOptions};
+    use std::io::{self, Write};
+    use std::os::unix::fs::OpenOptionsExt;
+    use std::os::unix::io::AsRawFd;
+
+    use super::{DeviceFactory, DeviceSpec, EventWriter, EV_ABS, EV_KEY};
+
+    // ioctl numbers from linux/uinput.h (_IO / _IOW with type 'U')
+    const fn io(nr: u32) -> u32 {
+        (b'U' as u32) << 8 | nr
+    }
+
+    const fn iow<T>(nr: u32) -> u32 {
+        1 << 30 | (std::mem::size_of::<T>() as u32) << 16 | io(nr)
+    }
+
+    const UI_DEV_CREATE: u32 = io(1);
+    const UI_DEV_DESTROY: u32 = io(2);
+    const UI_DEV_SETUP: u32 = iow::<libc::uinput_setup>(3);
+    const UI_ABS_SETUP: u32 = iow::<libc::uinput_abs_setup>(4);
+    const UI_SET_EVBIT: u32 = iow::<libc::c_int>(100);
+    const UI_SET_KEYBIT: u32 = iow::<libc::c_int>(101);
+    const UI_SET_ABSBIT: u32 = iow::<libc::c_int>(103);
+    const BUS_VIRTUAL: u16 = 0x06;
+
+    fn check(ret: libc::c_int) -> io::Result<()> {
+        if ret < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(())
+        }
+    }
+
+    fn open() -> io::Result<File> {
+        OpenOptions::new()
+            .write(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open("/dev/uinput")
+    }
+
+    // Fails early when the module isn't loaded or the user lacks access
+    // (usually a udev rule granting the `input` group write access)
+    pub fn check_access() -> io::Result<()> {
+        open().map(drop)
+    }
+
+    pub struct Uinput;
+
+    impl DeviceFactory for Uinput {
+        fn create(&mut self, spec: &DeviceSpec) -> io::Result<Box<dyn EventWriter>> {
+            let file = open()?;
+            let fd = file.as_raw_fd();
+
+            // SAFETY: `fd` is an open uinput device and every pointer passed
+            // refers to a live, correctly sized struct from linux/uinput.h
+            unsafe {
+                check(libc::ioctl(fd, UI_SET_EVBIT as _, EV_KEY as libc::c_int))?;
+                for &key in &spec.keys {
+                    check(libc::ioctl(fd, UI_SET_KEYBIT as _, key as libc::c_int))?;
+                }
+
+                if !spec.axes.is_empty() {
+                    check(libc::ioctl(fd, UI_SET_EVBIT as _, EV_ABS as libc::c_int))?;
+                }
+                for axis in &spec.axes {
+                    check(libc::ioctl(fd, UI_SET_ABSBIT as _, axis.code as libc::c_int))?;
+                    let mut abs: libc::uinput_abs_setup = std::mem::zeroed();
+                    abs.code = axis.code;
+                    abs.absinfo.minimum = axis.min;
+                    abs.absinfo.maximum = axis.max;
+                    check(libc::ioctl(fd, UI_ABS_SETUP as _, &abs))?;
+                }
+
+                let mut setup: libc::uinput_setup = std::mem::zeroed();
+                setup.id.bustype = BUS_VIRTUAL;
+                setup.id.vendor = spec.vendor;
+                setup.id.product = spec.product;
+                setup.id.version = 1;
+                // Leaves room for the terminating NUL
+                for (dst, src) in setup.name.iter_mut().zip(spec.name.bytes().take(libc::UINPUT_MAX_NAME_SIZE - 1)) {
+                    *dst = src as libc::c_char;
+                }
+                check(libc::ioctl(fd, UI_DEV_SETUP as _, &setup))?;
+                check(libc::ioctl(fd, UI_DEV_CREATE as _))?;
+            }
+
+            Ok(Box::new(UinputDevice { file }))
+        }
+    }
+
+    struct UinputDevice {
+        file: File,
+    }
+
+    impl EventWriter for UinputDevice {
+        fn write_event(&mut self, kind: u16, code: u16, value: i32) -> io::Result<()> {
+            // SAFETY: input_event is plain old data; the kernel fills in the
+            // timestamp when it is left zeroed
+            let bytes = unsafe {
+                let mut event: libc::input_event = std::mem::zeroed();
+                event.type_ = kind;
+                event.code = code;
+                event.value = value;
+                std::slice::from_raw_parts(
+                    &event as *const libc::input_event as *const u8,
+                    std::mem::size_of::<libc::input_event>(),
+                )
+                .to_vec()
+            };
+            self.file.write_all(&bytes)
+        }
+    }
+
+    impl Drop for UinputDevice {
+        fn drop(&mut self) {
+            // SAFETY: the fd stays open until `file` is dropped after this
+            unsafe {
+                libc::ioctl(self.file.as_raw_fd(), UI_DEV_DESTROY as _);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gamepads() -> (VirtualGamepads, MemoryDevices) {
+        let devices = MemoryDevices::new();
+        (VirtualGamepads::new(Box::new(devices.clone())), devices)
+    }
+
+    fn event(device: usize, kind: u16, code: u16, value: i32) -> MemoryEvent {
+        MemoryEvent { device, kind, code, value }
+    }
+
+    fn syn(device: usize) -> MemoryEvent {
+        event(device, EV_SYN, SYN_REPORT, 0)
+    }
+
+    #[test]
+    fn one_device_per_slot() {
+        let (mut pads, devices) = gamepads();
+        pads.connect(0).unwrap();
+        pads.connect(2).unwrap();
+        pads.connect(0).unwrap();
+        pads.press(2, Action::Green).unwrap();
+
+        let specs = devices.devices();
+        assert_eq!(specs.len(), 2);
+        assert_eq!(specs[0], DeviceSpec::guitar(0));
+        assert_eq!(specs[0].name, "MobileHero Guitar (Player 1)");
+        assert_eq!(specs[1].name, "MobileHero Guitar (Player 3)");
+        assert_eq!((specs[1].vendor, specs[1].product), (VENDOR_ID, PRODUCT_ID));
+    }
+
+    #[test]
+    fn spec_declares_every_control() {
+        let spec = DeviceSpec::guitar(0);
+        for action in Action::ALL {
+            match control(action) {
+                Control::Button(code) => assert!(spec.keys.contains(&code), "{} not declared", action.name()),
+                Control::Axis(code) => assert!(spec.axes.iter().any(|a| a.code == code), "{} not declared", action.name()),
+            }
+        }
+        assert!(spec.axes.iter().all(|a| (a.min, a.max) == (AXIS_MIN, AXIS_MAX)));
+    }
+
+    #[test]
+    fn press_and_release_are_reported() {
+        let (mut pads, devices) = gamepads();
+        pads.connect(0).unwrap();
+        pads.connect(1).unwrap();
+        pads.press(1, Action::Green).unwrap();
+        pads.release(1, Action::Green).unwrap();
+        pads.press(0, Action::StrumDown).unwrap();
+        // Axis controls ignore button input
+        pads.press(0, Action::Whammy).unwrap();
+
+        assert_eq!(
+            devices.events(),
+            [
+                event(1, EV_KEY, BTN_SOUTH, 1),
+                syn(1),
+                event(1, EV_KEY, BTN_SOUTH, 0),
+                syn(1),
+                event(0, EV_KEY, BTN_DPAD_DOWN, 1),
+                syn(0),
+            ]
+        );
+    }
+
+    #[test]
+    fn shared_buttons_stay_down_until_the_last_release() {
+        let (mut pads, devices) = gamepads();
+        pads.press(0, Action::StrumUp).unwrap();
+        pads.press(0, Action::Up).unwrap();
+        // A repeated press doesn't count twice
+        pads.press(0, Action::Up).unwrap();
+        pads.release(0, Action::Up).unwrap();
+        // Auto-repeat toggles the nav action under the held strum
+        pads.press(0, Action::Up).unwrap();
+        pads.release(0, Action::Up).unwrap();
+        assert_eq!(devices.events(), [event(0, EV_KEY, BTN_DPAD_UP, 1), syn(0)]);
+
+        pads.release(0, Action::StrumUp).unwrap();
+        // Releasing something not held changes nothing
+        pads.release(0, Action::StrumUp).unwrap();
+        pads.release(0, Action::Up).unwrap();
+        assert_eq!(
+            devices.events(),
+            [
+                event(0, EV_KEY, BTN_DPAD_UP, 1),
+                syn(0),
+                event(0, EV_KEY, BTN_DPAD_UP, 0),
+                syn(0),
+            ]
+        );
+
+        // Frets and drum pads share the face buttons the same way
+        pads.press(0, Action::Green).unwrap();
+        pads.press(0, Action::DrumGreen).unwrap();
+        pads.release_all(0);
+        pads.press(0, Action::DrumGreen).unwrap();
+        let presses = devices
+            .events()
+            .iter()
+            .filter(|e| (e.kind, e.code, e.value) == (EV_KEY, BTN_SOUTH, 1))
+            .count();
+        assert_eq!(presses, 2);
+    }
+
+    #[test]
+    fn axis_scales_to_full_range() {
+        let (mut pads, devices) = gamepads();
+        for value in [1.0, -1.0, 0.5, 0.0, 3.0, -3.0] {
+            pads.axis(0, Action::Whammy, value).unwrap();
+        }
+        pads.axis(0, Action::Tilt, 1.0).unwrap();
+        // Buttons ignore axis input
+        pads.axis(0, Action::Green, 1.0).unwrap();
+
+        let values: Vec<(u16, i32)> = devices
+            .events()
+            .iter()
+            .filter(|e| e.kind == EV_ABS)
+            .map(|e| (e.code, e.value))
+            .collect();
+        assert_eq!(
+            values,
+            [
+                (ABS_RX, AXIS_MAX),
+                (ABS_RX, -AXIS_MAX),
+                (ABS_RX, AXIS_MAX / 2),
+                (ABS_RX, 0),
+                (ABS_RX, AXIS_MAX),
+                (ABS_RX, -AXIS_MAX),
+                (ABS_RY, AXIS_MAX),
+            ]
+        );
+        assert_eq!(devices.events().iter().filter(|e| e.kind == EV_SYN).count(), 7);
+    }
+
+    #[test]
+    fn release_all_lets_go_of_one_player() {
+        let (mut pads, devices) = gamepads();
+        pads.press(0, Action::Red).unwrap();
+        pads.press(0, Action::Starpower).unwrap();
+        pads.release(0, Action::Starpower).unwrap();
+        pads.axis(0, Action::Whammy, 0.8).unwrap();
+        pads.press(1, Action::Blue).unwrap();
+        let before = devices.events().len();
+
+        pads.release_all(0);
+        // Players without a device have nothing to release
+        pads.release_all(5);
+
+        assert_eq!(
+            devices.events()[before..],
+            [
+                event(0, EV_KEY, BTN_EAST, 0),
+                event(0, EV_ABS, ABS_RX, 0),
+                event(0, EV_ABS, ABS_RY, 0),
+                syn(0),
+            ]
+        );
+        assert_eq!(devices.devices().len(), 2);
+
+        // Nothing left to release the second time
+        pads.release_all(0);
+        assert_eq!(
+            devices.events()[before + 4..],
+            [event(0, EV_ABS, ABS_RX, 0), event(0, EV_ABS, ABS_RY, 0), syn(0)]
+        );
+    }
+}
@@ -0,0 +1,315 @@
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+use tauri::{Emitter, Manager}; // Need Manager trait for checking app state if needed, but path() is on AppHandle
+
+use crate::config::{self, Config};
+use crate::events::{EventSink, ServerEvent};
+use crate::injector::InjectorStats;
+use crate::keys::{self, KeyGroup};
+use crate::websocket::{self, ServerState};
+use crate::{auth, network, pairing, qr};
+
+// Helper to get config path
+fn get_config_path(app: &tauri::AppHandle) -> std::path::PathBuf {
+    // For Tauri v2, we can use:
+    // app.path().app_config_dir() -> Result<PathBuf>
+    
+    // Note: handling unwraps carefully.
+    match app.path().app_config_dir() {
+        Ok(dir) => dir.join("config.json"),
+        Err(_) => {
+            // Fallback to local execution dir if app_config_dir fails (e.g. unexpected environment)
+            std::env::current_exe()
+                .ok()
+                .and_then(|p| p.parent().map(|p| p.to_path_buf()))
+                .unwrap_or_else(|| std::path::PathBuf::from("."))
+                .join("config.json")
+        }
+    }
+}
+
+#[derive(Clone, serde::Serialize)]
+struct ForcedReleasePayload {
+    addr: String,
+    action: &'static str,
+}
+
+// Forwards server events to the webview, echoing log lines to stdout
+struct TauriSink {
+    app: tauri::AppHandle,
+}
+
+impl EventSink for TauriSink {
+    fn emit(&self, event: ServerEvent) {
+        let _ = match event {
+            ServerEvent::Log(line) => {
+                println!("{}", line);
+                self.app.emit("log", line)
+            }
+            ServerEvent::Status(status) => self.app.emit("server-status", status.as_str()),
+            ServerEvent::ClientCount(count) => self.app.emit("client-count", count),
+            ServerEvent::ClientAuthenticated(addr) => self.app.emit("client-authenticated", addr.to_string()),
+            ServerEvent::ClientDisconnected(addr) => self.app.emit("client-disconnected", addr.to_string()),
+            ServerEvent::ForcedRelease(addr, action) => self.app.emit(
+                "forced-release",
+                ForcedReleasePayload {
+                    addr: addr.to_string(),
+                    action: action.name(),
+                },
+            ),
+        };
+    }
+}
+
+#[tauri::command]
+async fn start_server(
+    state: tauri::State<'_, Arc<Mutex<ServerState>>>,
+    app: tauri::AppHandle,
+) -> Result<String, String> {
+    let config = config::load_config(Some(get_config_path(&app)));
+    // Hashed before taking the lock
+    let session_pin = if config.randomize_pin {
+        Some(auth::generate_session_pin().await)
+    } else {
+        None
+    };
+    
+    let mut server = state.lock().await;
+    
+    if server.is_running {
+        return Err("Server already running".to_string());
+    }
+    
+    let port = config.port;
+    let bind = network::BindTarget::from_config(&config)?;
+    let dual_stack = config.dual_stack;
+
+    // Start the WebSocket server in a background task
+    let state_clone = Arc::clone(&state.inner());
+    let app_clone = app.clone();
+    
+    tokio::spawn(async move {
+        let sink = Arc::new(TauriSink { app: app_clone });
+        if let Err(e) = websocket::run_server(state_clone, sink, config).await {
+            eprintln!("Server error: {}", e);
+        }
+    });
+    
+    server.is_running = true;
+    server.should_stop = false; // Reset shutdown flag
+    server.output = None; // Rebuilt from the current config
+    
+    match session_pin {
+        Some((pin, hash)) => server.set_active_pin(pin, hash, false),
+        None => server.clear_active_pin(),
+    }
+    
+    // Address the phone should connect to for the chosen bind address
+    Ok(std::net::SocketAddr::new(bind.pairing_ip(dual_stack), port).to_string())
+}
+
+#[tauri::command]
+async fn stop_server(state: tauri::State<'_, Arc<Mutex<ServerState>>>) -> Result<(), String> {
+    let mut server = state.lock().await;
+    server.is_running = false;
+    server.should_stop = true;
+    server.clear_active_pin();
+    server.pairing_code = None;
+    
+    // Signal all connections to close
+    let _ = server.shutdown_tx.send(());
+    
+    Ok(())
+}
+
+// The session PIN if one was generated. The configured PIN is only stored
+// hashed, so there is nothing to return for it.
+#[tauri::command]
+async fn get_active_pin(
+    state: tauri::State<'_, Arc<Mutex<ServerState>>>,
+) -> Result<Option<String>, String> {
+    let server = state.lock().await;
+    Ok(server.active_pin.clone())
+}
+
+#[tauri::command]
+async fn rotate_pin(
+    state: tauri::State<'_, Arc<Mutex<ServerState>>>,
+    app: tauri::AppHandle,
+    disconnect_existing: bool,
+) -> Result<String, String> {
+    let (pin, hash) = auth::generate_session_pin().await;
+    state.lock().await.set_active_pin(pin.clone(), hash, disconnect_existing);
+    let _ = app.emit("log", "[OK] PIN rotated");
+    Ok(pin)
+}
+
+// Queue depth and timing of the input injector, while the server runs
+#[tauri::command]
+async fn get_injector_stats(
+    state: tauri::State<'_, Arc<Mutex<ServerState>>>,
+) -> Result<Option<InjectorStats>, String> {
+    let server = state.lock().await;
+    Ok(server.output.as_ref().map(|output| output.stats()))
+}
+
+#[tauri::command]
+fn list_network_interfaces() -> Vec<network::NetworkInterface> {
+    network::list_interfaces()
+}
+
+#[tauri::command]
+fn get_local_ip() -> String {
+    network::BindTarget::All.pairing_ip(false).to_string()
+}
+
+// Every key name a binding may use, grouped for the settings UI
+#[tauri::command]
+fn list_key_names() -> Vec<KeyGroup> {
+    keys::catalog()
+}
+
+#[tauri::command]
+fn get_config(app: tauri::AppHandle) -> Config {
+    let path = get_config_path(&app);
+    config::load_config(Some(path)).redacted()
+}
+
+#[tauri::command]
+async fn save_config(
+    state: tauri::State<'_, Arc<Mutex<ServerState>>>,
+    app: tauri::AppHandle,
+    mut config: Config,
+) -> Result<(), String> {
+    let path = get_config_path(&app);
+    // The webview never sees the stored hash; an empty PIN means "unchanged".
+    // A new one is hashed on the blocking pool so save_config has nothing
+    // left to derive.
+    if config.pin.is_empty() {
+        config.pin_hash = config::load_config(Some(path.clone())).pin_hash;
+    } else {
+        let pin = std::mem::take(&mut config.pin);
+        config.pin_hash = Some(auth::PinHash::new_blocking(pin).await);
+    }
+    config::save_config(&config, path.clone()).map_err(|e| e.to_string())?;
+    
+    // Connections made from now on pick up the new settings
+    let mut server = state.lock().await;
+    if server.is_running {
+        let config = config::load_config(Some(path));
+        if let Some(output) = &server.output {
+            output.configure(&config);
+        }
+        server.config = Arc::new(config);
+    }
+    Ok(())
+}
+
+#[tauri::command]
+fn generate_qr_code(data: String, options: Option<qr::QrOptions>) -> Result<String, String> {
+    qr::render(&data, &options.unwrap_or_default())
+}
+
+#[derive(serde::Serialize)]
+struct PairingPayload {
+    uri: String,
+    qr: String,
+}
+
+// Builds the pairing URI for the running server and renders it as a QR code
+#[tauri::command]
+async fn get_pairing_info(
+    state: tauri::State<'_, Arc<Mutex<ServerState>>>,
+    app: tauri::AppHandle,
+    options: Option<qr::QrOptions>,
+) -> Result<PairingPayload, String> {
+    let config = config::load_config(Some(get_config_path(&app)));
+    let pin = state.lock().await.active_pin.clone();
+    let info = pairing::PairingInfo::for_server(&config, pin)?;
+    
+    let uri = info.to_uri();
+    let qr = qr::render(&uri, &options.unwrap_or_default())?;
+    Ok(PairingPayload { uri, qr })
+}
+
+#[derive(serde::Serialize)]
+struct PairingCodePayload {
+    code: String,
+    expires_in: u64,
+}
+
+// Issues a one-time code for typing in by hand, replacing any earlier one
+#[tauri::command]
+async fn generate_pairing_code(
+    state: tauri::State<'_, Arc<Mutex<ServerState>>>,
+    app: tauri::AppHandle,
+) -> Result<PairingCodePayload, String> {
+    if !state.lock().await.is_running {
+        return Err("Start the server before issuing a pairing code".to_string());
+    }
+    let config = config::load_config(Some(get_config_path(&app)));
+    let bind = network::BindTarget::from_config(&config)?;
+    let host = bind
+        .pairing_hosts(false)
+        .into_iter()
+        .find_map(|ip| match ip {
+            std::net::IpAddr::V4(v4) => Some(v4),
+            std::net::IpAddr::V6(_) => None,
+        })
+        .ok_or_else(|| "Pairing codes need an IPv4 address".to_string())?;
+    // The code only carries the last octet, which the phone combines with
+    // its own network; a loopback-only server is unreachable from it
+    if host.is_loopback() {
+        return Err("Pairing codes are unavailable in loopback-only mode".to_string());
+    }
+    
+    let issued = pairing::IssuedPairingCode::new(host, config.port);
+    let code = issued.code().encode();
+    state.lock().await.pairing_code = Some(issued);
+    
+    let _ = app.emit("log", "[OK] New pairing code issued");
+    Ok(PairingCodePayload {
+        code,
+        expires_in: pairing::PAIRING_CODE_TTL.as_secs(),
+    })
+}
+
+#[cfg_attr(mobile, tauri::mobile_entry_point)]
+pub fn run() {
+    let server_state = Arc::new(Mutex::new(ServerState::new()));
+    
+    tauri::Builder::default()
+        .plugin(tauri_plugin_opener::init())
+        .manage(server_state)
+        .invoke_handler(tauri::generate_handler![
+            start_server,
+            stop_server,
+            get_active_pin,
+            rotate_pin,
+            get_injector_stats,
+            get_local_ip,
+            list_network_interfaces,
+            get_config,
+            save_config,
+            list_key_names,
+            generate_qr_code,
+            get_pairing_info,
+            generate_pairing_code,
+        ])
+        .setup(|_app| {
+            // Initialize the tokio runtime for async operations
+            let rt = tokio::runtime::Runtime::new().expect("Failed to create Tokio runtime");
+            std::thread::spawn(move || {
+                rt.block_on(async {
+                    // Keep runtime alive
+                    loop {
+                        tokio::time::sleep(tokio::time::Duration::from_secs(3600)).await;
+                    }
+                });
+            });
+            Ok(())
+        })
+        .run(tauri::generate_context!())
+        .expect("error while running tauri application");
+}
@@ -0,0 +1,167 @@
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+use crate::auth::{self, PinHash};
+use crate::config;
+use crate::events::{EventSink, ServerEvent};
+use crate::network::BindTarget;
+use crate::pairing::PairingInfo;
+#[cfg(feature = "qr")]
+use crate::qr::{self, QrFormat, QrOptions};
+use crate::websocket::{self, ServerState};
+
+// Same as the `identifier` in tauri.conf.json, so the headless server and the
+// launcher share one config file
+const APP_IDENTIFIER: &str = "com.mobilehero.launcher";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    // Every button press
+    Debug,
+}
+
+impl LogLevel {
+    // Server log lines carry their severity as a "[TAG]" prefix
+    fn of(line: &str) -> Self {
+        let tag = line
+            .strip_prefix('[')
+            .and_then(|rest| rest.split_once(']'))
+            .map(|(tag, _)| tag)
+            .unwrap_or("");
+        match tag {
+            "ERROR" => LogLevel::Error,
+            "WARN" | "LOCKOUT" | "X" | "TIMEOUT" => LogLevel::Warn,
+            "FRET" | "STRUM" | "DRUM" | "ACTION" | "NAV" => LogLevel::Debug,
+            _ => LogLevel::Info,
+        }
+    }
+}
+
+impl FromStr for LogLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "error" => Ok(LogLevel::Error),
+            "warn" | "warning" => Ok(LogLevel::Warn),
+            "info" => Ok(LogLevel::Info),
+            "debug" => Ok(LogLevel::Debug),
+            _ => Err(format!("Unknown log level: {} (expected error, warn, info or debug)", s)),
+        }
+    }
+}
+
+// Command line overrides; anything left as None comes from the config file.
+// Overrides apply to this run only and are never written back.
+#[derive(Debug, Clone)]
+pub struct HeadlessOptions {
+    pub config_path: Option<PathBuf>,
+    pub port: Option<u16>,
+    pub bind_address: Option<String>,
+    // Session PIN instead of the configured one
+    pub pin: Option<String>,
+    pub log_level: LogLevel,
+}
+
+// Where the launcher keeps its config (Tauri's app config dir)
+pub fn default_config_path() -> PathBuf {
+    dirs::config_dir()
+        .map(|dir| dir.join(APP_IDENTIFIER))
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("config.json")
+}
+
+// Prints log lines at or above its level to the terminal
+pub struct TerminalSink {
+    pub level: LogLevel,
+}
+
+impl EventSink for TerminalSink {
+    fn emit(&self, event: ServerEvent) {
+        if let ServerEvent::Log(line) = event {
+            if LogLevel::of(&line) <= self.level {
+                println!("{}", line);
+            }
+        }
+    }
+
+    fn wants_input_logs(&self) -> bool {
+        self.level >= LogLevel::Debug
+    }
+}
+
+// Runs the server until Ctrl+C, printing the pairing QR code (with the `qr`
+// feature) and URI to the terminal
+pub async fn run(options: HeadlessOptions) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let config_path = options.config_path.unwrap_or_else(default_config_path);
+    let mut config = config::load_config(Some(config_path.clone()));
+    if let Some(port) = options.port {
+        config.port = port;
+    }
+    if let Some(bind_address) = options.bind_address {
+        config.bind_address = bind_address;
+    }
+    let bind = BindTarget::from_config(&config)?;
+
+    let state = Arc::new(Mutex::new(ServerState::new()));
+    let session_pin = match options.pin {
+        Some(pin) if !pin.is_empty() => {
+            let hash = PinHash::new_blocking(pin.clone()).await;
+            Some((pin, hash))
+        }
+        _ if config.randomize_pin => Some(auth::generate_session_pin().await),
+        _ => None,
+    };
+    let session_pin = {
+        let mut server = state.lock().await;
+        server.is_running = true;
+        session_pin.map(|(pin, hash)| {
+            server.set_active_pin(pin.clone(), hash, false);
+            pin
+        })
+    };
+
+    let info = PairingInfo::for_server(&config, session_pin.clone())?;
+    #[cfg(feature = "qr")]
+    {
+        let qr_options = QrOptions {
+            format: QrFormat::Text,
+            ..Default::default()
+        };
+        println!("{}", qr::render(&info.to_uri(), &qr_options)?);
+    }
+    println!("Pairing URI: {}", info);
+    println!("Address: {}", SocketAddr::new(bind.pairing_ip(config.dual_stack), config.port));
+    match &session_pin {
+        Some(pin) => println!("PIN: {}", pin),
+        // Only its hash is stored, so there is nothing to show
+        None => println!(
+            "PIN: the configured one (pass --pin or MOBILEHERO_PIN, or enable randomize_pin, to have it printed here)"
+        ),
+    }
+    println!("Config: {}", config_path.display());
+    println!();
+
+    let sink = Arc::new(TerminalSink { level: options.log_level });
+    let mut server = tokio::spawn(websocket::run_server(Arc::clone(&state), sink, config));
+
+    tokio::select! {
+        // The server only returns on its own if it failed to start
+        result = &mut server => return result?,
+        _ = tokio::signal::ctrl_c() => {}
+    }
+
+    {
+        let mut server = state.lock().await;
+        server.is_running = false;
+        server.should_stop = true;
+        let _ = server.shutdown_tx.send(());
+    }
+    server.await?
+}
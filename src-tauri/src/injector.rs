@@ -0,0 +1,286 @@
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::bindings::Action;
+use crate::config::Config;
+use crate::events::EventSink;
+use crate::output::OutputBackend;
+
+enum Command {
+    Connect(u8),
+    Press(u8, Action),
+    Release(u8, Action),
+    Axis(u8, Action, f32),
+    ReleaseAll(u8),
+    Configure(Box<Config>),
+}
+
+struct Injection {
+    command: Command,
+    queued_at: Instant,
+}
+
+#[derive(Default)]
+struct Counters {
+    queue_depth: AtomicU64,
+    max_queue_depth: AtomicU64,
+    injections: AtomicU64,
+    failures: AtomicU64,
+    // Time spent waiting in the queue and inside the backend call
+    total_wait_ns: AtomicU64,
+    max_wait_ns: AtomicU64,
+    total_inject_ns: AtomicU64,
+    max_inject_ns: AtomicU64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct InjectorStats {
+    pub queue_depth: u64,
+    pub max_queue_depth: u64,
+    pub injections: u64,
+    pub failures: u64,
+    pub avg_wait_us: u64,
+    pub max_wait_us: u64,
+    pub avg_inject_us: u64,
+    pub max_inject_us: u64,
+}
+
+// Hands input to a dedicated thread that owns the output backend, so async
+// tasks never block on OS input calls or contend for a lock. Cheap to clone;
+// the thread exits (dropping the backend) once every handle is gone.
+#[derive(Clone)]
+pub struct Injector {
+    tx: Sender<Injection>,
+    counters: Arc<Counters>,
+}
+
+impl Injector {
+    pub fn spawn(backend: Box<dyn OutputBackend>, sink: Arc<dyn EventSink>) -> Self {
+        let (tx, rx) = mpsc::channel();
+        let counters = Arc::new(Counters::default());
+        let thread_counters = Arc::clone(&counters);
+
+        std::thread::Builder::new()
+            .name("input-injector".to_string())
+            .spawn(move || {
+                raise_priority();
+                run(backend, rx, thread_counters, sink);
+            })
+            .expect("Failed to spawn input injector thread");
+
+        Self { tx, counters }
+    }
+
+    pub fn connect(&self, player: u8) {
+        self.send(Command::Connect(player));
+    }
+
+    pub fn press(&self, player: u8, action: Action) {
+        self.send(Command::Press(player, action));
+    }
+
+    pub fn release(&self, player: u8, action: Action) {
+        self.send(Command::Release(player, action));
+    }
+
+    pub fn axis(&self, player: u8, action: Action, value: f32) {
+        self.send(Command::Axis(player, action, value));
+    }
+
+    pub fn release_all(&self, player: u8) {
+        self.send(Command::ReleaseAll(player));
+    }
+
+    pub fn configure(&self, config: &Config) {
+        self.send(Command::Configure(Box::new(config.clone())));
+    }
+
+    pub fn stats(&self) -> InjectorStats {
+        let c = &self.counters;
+        let injections = c.injections.load(Ordering::Relaxed);
+        let avg_us = |total: &AtomicU64| total.load(Ordering::Relaxed) / injections.max(1) / 1000;
+        InjectorStats {
+            queue_depth: c.queue_depth.load(Ordering::Relaxed),
+            max_queue_depth: c.max_queue_depth.load(Ordering::Relaxed),
+            injections,
+            failures: c.failures.load(Ordering::Relaxed),
+            avg_wait_us: avg_us(&c.total_wait_ns),
+            max_wait_us: c.max_wait_ns.load(Ordering::Relaxed) / 1000,
+            avg_inject_us: avg_us(&c.total_inject_ns),
+            max_inject_us: c.max_inject_ns.load(Ordering::Relaxed) / 1000,
+        }
+    }
+
+    fn send(&self, command: Command) {
+        let depth = self.counters.queue_depth.fetch_add(1, Ordering::Relaxed) + 1;
+        self.counters.max_queue_depth.fetch_max(depth, Ordering::Relaxed);
+        let injection = Injection {
+            command,
+            queued_at: Instant::now(),
+        };
+        if self.tx.send(injection).is_err() {
+            // The thread is gone (it panicked); nothing left to deliver to
+            self.counters.queue_depth.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+}
+
+fn run(
+    mut backend: Box<dyn OutputBackend>,
+    rx: Receiver<Injection>,
+    counters: Arc<Counters>,
+    sink: Arc<dyn EventSink>,
+) {
+    while let Ok(injection) = rx.recv() {
+        counters.queue_depth.fetch_sub(1, Ordering::Relaxed);
+        let started = Instant::now();
+
+        let result = match injection.command {
+            Command::Connect(player) => backend.connect(player),
+            Command::Press(player, action) => backend.press(player, action),
+            Command::Release(player, action) => backend.release(player, action),
+            Command::Axis(player, action, value) => backend.axis(player, action, value),
+            Command::ReleaseAll(player) => {
+                backend.release_all(player);
+                Ok(())
+            }
+            Command::Configure(config) => {
+                backend.configure(&config);
+                continue;
+            }
+        };
+
+        let wait_ns = started.duration_since(injection.queued_at).as_nanos() as u64;
+        let inject_ns = started.elapsed().as_nanos() as u64;
+        counters.injections.fetch_add(1, Ordering::Relaxed);
+        counters.total_wait_ns.fetch_add(wait_ns, Ordering::Relaxed);
+        counters.max_wait_ns.fetch_max(wait_ns, Ordering::Relaxed);
+        counters.total_inject_ns.fetch_add(inject_ns, Ordering::Relaxed);
+        counters.max_inject_ns.fetch_max(inject_ns, Ordering::Relaxed);
+
+        if let Err(e) = result {
+            counters.failures.fetch_add(1, Ordering::Relaxed);
+            sink.log(format!("[ERROR] {}", e));
+        }
+    }
+}
+
+// Best effort: a busy game shouldn't delay our key presses. Failing (e.g. no
+// permission to raise priority on Linux) just leaves the default.
+fn raise_priority() {
+    #[cfg(windows)]
+    // SAFETY: GetCurrentThread returns a pseudo handle that needs no cleanup
+    unsafe {
+        use windows_sys::Win32::System::Threading::{GetCurrentThread, SetThreadPriority, THREAD_PRIORITY_HIGHEST};
+        SetThreadPriority(GetCurrentThread(), THREAD_PRIORITY_HIGHEST);
+    }
+
+    #[cfg(target_os = "linux")]
+    // SAFETY: on Linux, who = 0 with PRIO_PROCESS means the calling thread
+    unsafe {
+        libc::setpriority(libc::PRIO_PROCESS as _, 0, -10);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::RecordingSink;
+    use crate::output::{OutputEvent, RecordingOutput};
+    use std::time::Duration;
+
+    // Fails every press and reports when the injector thread drops it
+    struct Failing {
+        dropped: mpsc::Sender<()>,
+    }
+
+    impl OutputBackend for Failing {
+        fn press(&mut self, _player: u8, action: Action) -> Result<(), String> {
+            Err(format!("cannot press {}", action.name()))
+        }
+
+        fn release(&mut self, _player: u8, _action: Action) -> Result<(), String> {
+            Ok(())
+        }
+
+        fn axis(&mut self, _player: u8, _action: Action, _value: f32) -> Result<(), String> {
+            Ok(())
+        }
+    }
+
+    impl Drop for Failing {
+        fn drop(&mut self) {
+            let _ = self.dropped.send(());
+        }
+    }
+
+    fn wait_until(condition: impl Fn() -> bool) {
+        for _ in 0..200 {
+            if condition() {
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        panic!("condition not met in time");
+    }
+
+    #[test]
+    fn delivers_in_order_and_counts() {
+        let output = RecordingOutput::new();
+        let injector = Injector::spawn(Box::new(output.clone()), Arc::new(RecordingSink::new()));
+        injector.connect(1);
+        injector.press(1, Action::Green);
+        injector.axis(1, Action::Whammy, 0.5);
+        injector.configure(&Config::default());
+        injector.release(1, Action::Green);
+        injector.release_all(1);
+
+        let expected = [
+            OutputEvent::Connect(1),
+            OutputEvent::Press(1, Action::Green),
+            OutputEvent::Axis(1, Action::Whammy, 0.5),
+            OutputEvent::Release(1, Action::Green),
+            OutputEvent::ReleaseAll(1),
+        ];
+        wait_until(|| output.events().len() == expected.len());
+        assert_eq!(output.events(), expected);
+
+        // Configure isn't an injection
+        wait_until(|| injector.stats().injections == 5);
+        let stats = injector.stats();
+        assert_eq!(stats.queue_depth, 0);
+        assert!((1..=6).contains(&stats.max_queue_depth));
+        assert_eq!(stats.failures, 0);
+        assert!(stats.avg_wait_us <= stats.max_wait_us);
+        assert!(stats.avg_inject_us <= stats.max_inject_us);
+    }
+
+    #[test]
+    fn reports_failures() {
+        let (dropped, _rx) = mpsc::channel();
+        let sink = Arc::new(RecordingSink::new());
+        let injector = Injector::spawn(Box::new(Failing { dropped }), sink.clone());
+        injector.press(0, Action::Red);
+        injector.release(0, Action::Red);
+
+        wait_until(|| injector.stats().injections == 2);
+        assert_eq!(injector.stats().failures, 1);
+        assert_eq!(sink.logs(), ["[ERROR] cannot press red"]);
+    }
+
+    #[test]
+    fn thread_drops_backend_once_every_handle_is_gone() {
+        let (dropped, rx) = mpsc::channel();
+        let injector = Injector::spawn(Box::new(Failing { dropped }), Arc::new(RecordingSink::new()));
+        let clone = injector.clone();
+        drop(injector);
+        clone.release(0, Action::Red);
+        assert!(rx.recv_timeout(Duration::from_millis(100)).is_err());
+
+        drop(clone);
+        rx.recv_timeout(Duration::from_secs(5)).expect("backend not dropped");
+    }
+}
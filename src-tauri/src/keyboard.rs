@@ -1,127 +1,570 @@
-use enigo::{Enigo, Key, Keyboard, Settings};
-use once_cell::sync::Lazy;
-use std::sync::Mutex;
+use enigo::{Direction, Enigo, Key, Keyboard, Settings};
+use std::collections::{HashMap, HashSet};
 
+use crate::bindings::{Action, BindingTable};
 use crate::config::Config;
+use crate::keys::{self, KeyCode};
+use crate::output::OutputBackend;
 
-static ENIGO: Lazy<Mutex<Enigo>> =
-    Lazy::new(|| Mutex::new(Enigo::new(&Settings::default()).expect("Failed to create Enigo")));
-
-fn string_to_key(key_str: &str) -> Option<Key> {
-    match key_str.to_lowercase().as_str() {
-        // Letters
-        "a" => Some(Key::Unicode('a')),
-        "b" => Some(Key::Unicode('b')),
-        "c" => Some(Key::Unicode('c')),
-        "d" => Some(Key::Unicode('d')),
-        "e" => Some(Key::Unicode('e')),
-        "f" => Some(Key::Unicode('f')),
-        "g" => Some(Key::Unicode('g')),
-        "h" => Some(Key::Unicode('h')),
-        "i" => Some(Key::Unicode('i')),
-        "j" => Some(Key::Unicode('j')),
-        "k" => Some(Key::Unicode('k')),
-        "l" => Some(Key::Unicode('l')),
-        "m" => Some(Key::Unicode('m')),
-        "n" => Some(Key::Unicode('n')),
-        "o" => Some(Key::Unicode('o')),
-        "p" => Some(Key::Unicode('p')),
-        "q" => Some(Key::Unicode('q')),
-        "r" => Some(Key::Unicode('r')),
-        "s" => Some(Key::Unicode('s')),
-        "t" => Some(Key::Unicode('t')),
-        "u" => Some(Key::Unicode('u')),
-        "v" => Some(Key::Unicode('v')),
-        "w" => Some(Key::Unicode('w')),
-        "x" => Some(Key::Unicode('x')),
-        "y" => Some(Key::Unicode('y')),
-        "z" => Some(Key::Unicode('z')),
-
-        // Special keys
-        "space" => Some(Key::Space),
-        "enter" | "return" => Some(Key::Return),
-        "escape" | "esc" => Some(Key::Escape),
-        "tab" => Some(Key::Tab),
-        "backspace" => Some(Key::Backspace),
-
-        // Arrow keys
-        "up" => Some(Key::UpArrow),
-        "down" => Some(Key::DownArrow),
-        "left" => Some(Key::LeftArrow),
-        "right" => Some(Key::RightArrow),
-
-        // Punctuation
-        ";" | "semicolon" => Some(Key::Unicode(';')),
-        "'" | "apostrophe" => Some(Key::Unicode('\'')),
-        "," | "comma" => Some(Key::Unicode(',')),
-        "." | "period" => Some(Key::Unicode('.')),
-        "/" | "slash" => Some(Key::Unicode('/')),
-
-        _ => {
-            // Try single character
-            if key_str.len() == 1 {
-                Some(Key::Unicode(key_str.chars().next().unwrap()))
-            } else {
-                None
-            }
+// Analog controls are held down past this deflection
+const AXIS_PRESS_THRESHOLD: f32 = 0.5;
+
+// What a binding sends: a key through the active layout, or a raw keycode
+// for physical bindings
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KeyOutput {
+    Key(Key),
+    Raw(u16),
+}
+
+// The chord a binding sends, or None if any key can't be sent here
+pub fn string_to_keys(binding: &str) -> Option<Vec<KeyOutput>> {
+    keys::parse_chord(binding)?.into_iter().map(key_output).collect()
+}
+
+fn key_output(code: KeyCode) -> Option<KeyOutput> {
+    match code {
+        KeyCode::Physical(c) => physical_keycode(c).map(KeyOutput::Raw),
+        code => to_enigo(code).map(KeyOutput::Key),
+    }
+}
+
+// None when this platform (or enigo) can't send the key
+fn to_enigo(code: KeyCode) -> Option<Key> {
+    let key = match code {
+        KeyCode::Char(c) => Key::Unicode(c),
+        KeyCode::Physical(_) => return None,
+        KeyCode::F(n) => return function_key(n),
+        KeyCode::Shift => Key::Shift,
+        KeyCode::LShift => Key::LShift,
+        KeyCode::RShift => Key::RShift,
+        KeyCode::Control => Key::Control,
+        KeyCode::LControl => Key::LControl,
+        KeyCode::RControl => Key::RControl,
+        KeyCode::Alt => Key::Alt,
+        #[cfg(windows)]
+        KeyCode::RAlt => Key::RMenu,
+        #[cfg(target_os = "macos")]
+        KeyCode::RAlt => Key::ROption,
+        #[cfg(all(unix, not(target_os = "macos")))]
+        KeyCode::RAlt => Key::Other(0xffea), // XK_Alt_R
+        KeyCode::Meta => Key::Meta,
+        KeyCode::Up => Key::UpArrow,
+        KeyCode::Down => Key::DownArrow,
+        KeyCode::Left => Key::LeftArrow,
+        KeyCode::Right => Key::RightArrow,
+        KeyCode::Home => Key::Home,
+        KeyCode::End => Key::End,
+        KeyCode::PageUp => Key::PageUp,
+        KeyCode::PageDown => Key::PageDown,
+        KeyCode::Space => Key::Space,
+        KeyCode::Enter => Key::Return,
+        KeyCode::Escape => Key::Escape,
+        KeyCode::Tab => Key::Tab,
+        KeyCode::Backspace => Key::Backspace,
+        KeyCode::Delete => Key::Delete,
+        #[cfg(not(target_os = "macos"))]
+        KeyCode::Insert => Key::Insert,
+        #[cfg(target_os = "macos")]
+        KeyCode::Insert => return None,
+        KeyCode::CapsLock => Key::CapsLock,
+        KeyCode::MediaPlayPause => Key::MediaPlayPause,
+        KeyCode::MediaNextTrack => Key::MediaNextTrack,
+        KeyCode::MediaPrevTrack => Key::MediaPrevTrack,
+        #[cfg(not(target_os = "macos"))]
+        KeyCode::MediaStop => Key::MediaStop,
+        #[cfg(target_os = "macos")]
+        KeyCode::MediaStop => return None,
+        KeyCode::VolumeUp => Key::VolumeUp,
+        KeyCode::VolumeDown => Key::VolumeDown,
+        KeyCode::VolumeMute => Key::VolumeMute,
+        KeyCode::Numpad(_)
+        | KeyCode::NumpadAdd
+        | KeyCode::NumpadSubtract
+        | KeyCode::NumpadMultiply
+        | KeyCode::NumpadDivide
+        | KeyCode::NumpadDecimal
+        | KeyCode::NumpadEnter => return numpad_key(code),
+    };
+    Some(key)
+}
+
+fn function_key(n: u8) -> Option<Key> {
+    let key = match n {
+        1 => Key::F1,
+        2 => Key::F2,
+        3 => Key::F3,
+        4 => Key::F4,
+        5 => Key::F5,
+        6 => Key::F6,
+        7 => Key::F7,
+        8 => Key::F8,
+        9 => Key::F9,
+        10 => Key::F10,
+        11 => Key::F11,
+        12 => Key::F12,
+        13 => Key::F13,
+        14 => Key::F14,
+        15 => Key::F15,
+        16 => Key::F16,
+        17 => Key::F17,
+        18 => Key::F18,
+        19 => Key::F19,
+        20 => Key::F20,
+        #[cfg(not(target_os = "macos"))]
+        21 => Key::F21,
+        #[cfg(not(target_os = "macos"))]
+        22 => Key::F22,
+        #[cfg(not(target_os = "macos"))]
+        23 => Key::F23,
+        #[cfg(not(target_os = "macos"))]
+        24 => Key::F24,
+        _ => return None,
+    };
+    Some(key)
+}
+
+// enigo only names numpad keys on Windows
+#[cfg(windows)]
+fn numpad_key(code: KeyCode) -> Option<Key> {
+    let key = match code {
+        KeyCode::Numpad(0) => Key::Numpad0,
+        KeyCode::Numpad(1) => Key::Numpad1,
+        KeyCode::Numpad(2) => Key::Numpad2,
+        KeyCode::Numpad(3) => Key::Numpad3,
+        KeyCode::Numpad(4) => Key::Numpad4,
+        KeyCode::Numpad(5) => Key::Numpad5,
+        KeyCode::Numpad(6) => Key::Numpad6,
+        KeyCode::Numpad(7) => Key::Numpad7,
+        KeyCode::Numpad(8) => Key::Numpad8,
+        KeyCode::Numpad(9) => Key::Numpad9,
+        KeyCode::NumpadAdd => Key::Add,
+        KeyCode::NumpadSubtract => Key::Subtract,
+        KeyCode::NumpadMultiply => Key::Multiply,
+        KeyCode::NumpadDivide => Key::Divide,
+        KeyCode::NumpadDecimal => Key::Decimal,
+        // Windows has no separate virtual key for it
+        KeyCode::NumpadEnter => Key::Return,
+        _ => return None,
+    };
+    Some(key)
+}
+
+// X11 keysyms (XK_KP_*)
+#[cfg(all(unix, not(target_os = "macos")))]
+fn numpad_key(code: KeyCode) -> Option<Key> {
+    let keysym = match code {
+        KeyCode::Numpad(n @ 0..=9) => 0xffb0 + n as u32,
+        KeyCode::NumpadAdd => 0xffab,
+        KeyCode::NumpadSubtract => 0xffad,
+        KeyCode::NumpadMultiply => 0xffaa,
+        KeyCode::NumpadDivide => 0xffaf,
+        KeyCode::NumpadDecimal => 0xffae,
+        KeyCode::NumpadEnter => 0xff8d,
+        _ => return None,
+    };
+    Some(Key::Other(keysym))
+}
+
+// macOS virtual key codes (kVK_ANSI_Keypad*)
+#[cfg(target_os = "macos")]
+fn numpad_key(code: KeyCode) -> Option<Key> {
+    let keycode = match code {
+        KeyCode::Numpad(0) => 0x52,
+        KeyCode::Numpad(1) => 0x53,
+        KeyCode::Numpad(2) => 0x54,
+        KeyCode::Numpad(3) => 0x55,
+        KeyCode::Numpad(4) => 0x56,
+        KeyCode::Numpad(5) => 0x57,
+        KeyCode::Numpad(6) => 0x58,
+        KeyCode::Numpad(7) => 0x59,
+        KeyCode::Numpad(8) => 0x5b,
+        KeyCode::Numpad(9) => 0x5c,
+        KeyCode::NumpadAdd => 0x45,
+        KeyCode::NumpadSubtract => 0x4e,
+        KeyCode::NumpadMultiply => 0x43,
+        KeyCode::NumpadDivide => 0x4b,
+        KeyCode::NumpadDecimal => 0x41,
+        KeyCode::NumpadEnter => 0x4c,
+        _ => return None,
+    };
+    Some(Key::Other(keycode))
+}
+
+// Windows takes the scan code itself
+#[cfg(windows)]
+fn physical_keycode(c: char) -> Option<u16> {
+    keys::scancode(c)
+}
+
+// X11 keycodes are evdev codes offset by 8
+#[cfg(all(unix, not(target_os = "macos")))]
+fn physical_keycode(c: char) -> Option<u16> {
+    keys::scancode(c).map(|code| code + 8)
+}
+
+// macOS virtual key codes (kVK_ANSI_*), which name positions, not characters
+#[cfg(target_os = "macos")]
+fn physical_keycode(c: char) -> Option<u16> {
+    let keycode = match c {
+        'a' => 0x00,
+        's' => 0x01,
+        'd' => 0x02,
+        'f' => 0x03,
+        'h' => 0x04,
+        'g' => 0x05,
+        'z' => 0x06,
+        'x' => 0x07,
+        'c' => 0x08,
+        'v' => 0x09,
+        'b' => 0x0b,
+        'q' => 0x0c,
+        'w' => 0x0d,
+        'e' => 0x0e,
+        'r' => 0x0f,
+        'y' => 0x10,
+        't' => 0x11,
+        '1' => 0x12,
+        '2' => 0x13,
+        '3' => 0x14,
+        '4' => 0x15,
+        '6' => 0x16,
+        '5' => 0x17,
+        '=' => 0x18,
+        '9' => 0x19,
+        '7' => 0x1a,
+        '-' => 0x1b,
+        '8' => 0x1c,
+        '0' => 0x1d,
+        ']' => 0x1e,
+        'o' => 0x1f,
+        'u' => 0x20,
+        '[' => 0x21,
+        'i' => 0x22,
+        'p' => 0x23,
+        'l' => 0x25,
+        'j' => 0x26,
+        '\'' => 0x27,
+        'k' => 0x28,
+        ';' => 0x29,
+        '\\' => 0x2a,
+        ',' => 0x2b,
+        '/' => 0x2c,
+        'n' => 0x2d,
+        'm' => 0x2e,
+        '.' => 0x2f,
+        '`' => 0x32,
+        _ => return None,
+    };
+    Some(keycode)
+}
+
+// Keyboard output through enigo, mapping controls with `Config.key_bindings`.
+// There is only one keyboard, so every player shares it.
+pub struct EnigoKeyboard {
+    sender: Box<dyn KeySender>,
+    // Each binding is a chord, usually of one key
+    keys: BindingTable<Vec<KeyOutput>>,
+    // Controls each player holds, buttons and axes past the threshold, so a
+    // disconnect can let go of them
+    held: HashSet<(u8, Action)>,
+    // How many held controls press each key. Chords share keys (Ctrl+1 and
+    // Ctrl+2) and players share the keyboard, so a key only goes up when the
+    // last control using it is released.
+    key_counts: HashMap<KeyOutput, u32>,
+}
+
+impl EnigoKeyboard {
+    // Fails without a display (or input permissions) instead of panicking
+    pub fn new(config: &Config) -> Result<Self, String> {
+        let enigo = Enigo::new(&Settings::default()).map_err(|e| format!("Failed to create Enigo: {}", e))?;
+        Ok(Self::with_sender(Box::new(enigo), config))
+    }
+
+    fn with_sender(sender: Box<dyn KeySender>, config: &Config) -> Self {
+        Self {
+            sender,
+            keys: BindingTable::resolve(config, string_to_keys).0,
+            held: HashSet::new(),
+            key_counts: HashMap::new(),
         }
     }
 }
 
-pub fn press_key(key_name: &str, config: &Config) {
-    // println!("[DEBUG] press_key called for: {}", key_name);
-    let binding = match config.key_bindings.get(key_name) {
-        Some(b) => b.clone(),
-        None => {
-            println!("[ERROR] No binding found for key: {}", key_name);
-            return;
+// Sends single key events: enigo for real, a recording in tests
+trait KeySender: Send {
+    fn send(&mut self, key: KeyOutput, direction: Direction) -> enigo::InputResult<()>;
+}
+
+impl KeySender for Enigo {
+    fn send(&mut self, key: KeyOutput, direction: Direction) -> enigo::InputResult<()> {
+        match key {
+            KeyOutput::Key(key) => self.key(key, direction),
+            KeyOutput::Raw(keycode) => self.raw(keycode, direction),
         }
-    };
+    }
+}
 
-    let key = match string_to_key(&binding) {
-        Some(k) => k,
-        None => {
-            println!("[ERROR] Failed to convert binding to Key: {}", binding);
-            return;
+// Presses the key unless something already holds it down. key_down and
+// key_up are free functions so callers can hold a borrow of the binding table.
+fn key_down(sender: &mut dyn KeySender, counts: &mut HashMap<KeyOutput, u32>, key: KeyOutput) -> enigo::InputResult<()> {
+    let count = counts.entry(key).or_insert(0);
+    if *count == 0 {
+        if let Err(e) = sender.send(key, Direction::Press) {
+            counts.remove(&key);
+            return Err(e);
         }
+    }
+    *counts.get_mut(&key).unwrap() += 1;
+    Ok(())
+}
+
+// Releases the key once nothing else holds it down
+fn key_up(sender: &mut dyn KeySender, counts: &mut HashMap<KeyOutput, u32>, key: KeyOutput) -> enigo::InputResult<()> {
+    let Some(count) = counts.get_mut(&key) else {
+        return Ok(());
     };
+    *count -= 1;
+    if *count > 0 {
+        return Ok(());
+    }
+    counts.remove(&key);
+    sender.send(key, Direction::Release)
+}
+
+impl OutputBackend for EnigoKeyboard {
+    fn press(&mut self, player: u8, action: Action) -> Result<(), String> {
+        if self.held.contains(&(player, action)) {
+            return Ok(());
+        }
+        let chord = self
+            .keys
+            .get(action)
+            .ok_or_else(|| format!("No key bound to {}", action.name()))?;
+        for (pressed, &key) in chord.iter().enumerate() {
+            if let Err(e) = key_down(self.sender.as_mut(), &mut self.key_counts, key) {
+                // Don't leave half a chord held
+                for &key in chord[..pressed].iter().rev() {
+                    let _ = key_up(self.sender.as_mut(), &mut self.key_counts, key);
+                }
+                return Err(format!("Failed to press key: {}", e));
+            }
+        }
+        self.held.insert((player, action));
+        Ok(())
+    }
 
-    // Press the key directly without state checking
-    match ENIGO.lock() {
-        Ok(mut enigo) => {
-            if let Err(e) = enigo.key(key, enigo::Direction::Press) {
-                println!("[ERROR] Failed to press key: {}", e);
+    fn release(&mut self, player: u8, action: Action) -> Result<(), String> {
+        if !self.held.remove(&(player, action)) {
+            return Ok(());
+        }
+        let Some(chord) = self.keys.get(action) else {
+            return Ok(());
+        };
+        // Release everything even if one key fails, reporting the first error
+        let mut result = Ok(());
+        for &key in chord.iter().rev() {
+            if let Err(e) = key_up(self.sender.as_mut(), &mut self.key_counts, key) {
+                result = result.and(Err(format!("Failed to release key: {}", e)));
             }
-            // println!("[DOWN] {}: {} -> {:?}", key_name, binding, key);
         }
-        Err(e) => println!("[ERROR] Failed to lock ENIGO: {}", e),
+        result
+    }
+
+    // Keys have no analog range, so the axis acts as a button
+    fn axis(&mut self, player: u8, action: Action, value: f32) -> Result<(), String> {
+        if self.keys.get(action).is_none() {
+            return Ok(());
+        }
+        let held = value.abs() >= AXIS_PRESS_THRESHOLD;
+        if held == self.held.contains(&(player, action)) {
+            return Ok(());
+        }
+        if held {
+            self.press(player, action)
+        } else {
+            self.release(player, action)
+        }
+    }
+
+    fn release_all(&mut self, player: u8) {
+        let actions: Vec<Action> = self
+            .held
+            .iter()
+            .filter(|(p, _)| *p == player)
+            .map(|&(_, action)| action)
+            .collect();
+        for action in actions {
+            let _ = self.release(player, action);
+        }
+    }
+
+    // Held controls are let go first, so their keys are released through the
+    // bindings they were pressed with
+    fn configure(&mut self, config: &Config) {
+        for (player, action) in self.held.clone() {
+            let _ = self.release(player, action);
+        }
+        self.keys = BindingTable::resolve(config, string_to_keys).0;
     }
 }
 
-pub fn release_key(key_name: &str, config: &Config) {
-    let binding = match config.key_bindings.get(key_name) {
-        Some(b) => b.clone(),
-        None => return,
-    };
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
 
-    let key = match string_to_key(&binding) {
-        Some(k) => k,
-        None => return,
-    };
+    type Sent = Arc<Mutex<Vec<(KeyOutput, Direction)>>>;
+
+    // Records key events, failing presses of `fail`
+    struct Recorder {
+        sent: Sent,
+        fail: Option<KeyOutput>,
+    }
 
-    // Release the key directly
-    match ENIGO.lock() {
-        Ok(mut enigo) => {
-            if let Err(e) = enigo.key(key, enigo::Direction::Release) {
-                println!("[ERROR] Failed to release key: {}", e);
+    impl KeySender for Recorder {
+        fn send(&mut self, key: KeyOutput, direction: Direction) -> enigo::InputResult<()> {
+            if direction == Direction::Press && Some(key) == self.fail {
+                return Err(enigo::InputError::Simulate("test failure"));
             }
-            // println!("[UP] {}: {} -> {:?}", key_name, binding, key);
+            self.sent.lock().unwrap().push((key, direction));
+            Ok(())
         }
-        Err(e) => println!("[ERROR] Failed to lock ENIGO for release: {}", e),
     }
-}
 
-pub fn release_all(_config: &Config) {
-    // PRESSED_KEYS tracking removed for performance/reliability.
-    // Client is responsible for releasing keys.
+    fn key(name: &str) -> KeyOutput {
+        string_to_keys(name).unwrap()[0]
+    }
+
+    fn config(bindings: &[(&str, &str)]) -> Config {
+        let mut config = Config::default();
+        for &(action, binding) in bindings {
+            config.key_bindings.insert(action.to_string(), binding.to_string());
+        }
+        config
+    }
+
+    fn keyboard(config: &Config, fail: Option<KeyOutput>) -> (EnigoKeyboard, Sent) {
+        let sent = Sent::default();
+        let recorder = Recorder { sent: Arc::clone(&sent), fail };
+        (EnigoKeyboard::with_sender(Box::new(recorder), config), sent)
+    }
+
+    fn take(sent: &Sent) -> Vec<(KeyOutput, Direction)> {
+        std::mem::take(&mut *sent.lock().unwrap())
+    }
+
+    #[test]
+    fn chords_share_keys_until_the_last_release() {
+        let config = config(&[("green", "Ctrl+1"), ("red", "Ctrl+2")]);
+        let (mut keyboard, sent) = keyboard(&config, None);
+        let (ctrl, one, two) = (key("Ctrl"), key("1"), key("2"));
+
+        keyboard.press(0, Action::Green).unwrap();
+        keyboard.press(0, Action::Red).unwrap();
+        assert_eq!(
+            take(&sent),
+            [(ctrl, Direction::Press), (one, Direction::Press), (two, Direction::Press)]
+        );
+
+        keyboard.release(0, Action::Green).unwrap();
+        assert_eq!(take(&sent), [(one, Direction::Release)]);
+        keyboard.release(0, Action::Red).unwrap();
+        assert_eq!(take(&sent), [(two, Direction::Release), (ctrl, Direction::Release)]);
+    }
+
+    #[test]
+    fn players_share_the_keyboard() {
+        // Default bindings: strum up and nav up are both the Up key
+        let (mut keyboard, sent) = keyboard(&Config::default(), None);
+        let up = key("Up");
+
+        keyboard.press(0, Action::StrumUp).unwrap();
+        keyboard.press(1, Action::StrumUp).unwrap();
+        keyboard.press(0, Action::Up).unwrap();
+        assert_eq!(take(&sent), [(up, Direction::Press)]);
+
+        keyboard.release(1, Action::StrumUp).unwrap();
+        keyboard.release(0, Action::StrumUp).unwrap();
+        assert!(take(&sent).is_empty());
+        keyboard.release(0, Action::Up).unwrap();
+        assert_eq!(take(&sent), [(up, Direction::Release)]);
+    }
+
+    // What nav auto-repeat sends while a strum on the same key is held
+    #[test]
+    fn repeat_is_silent_while_a_shared_key_is_held() {
+        let (mut keyboard, sent) = keyboard(&Config::default(), None);
+        keyboard.press(0, Action::StrumUp).unwrap();
+        keyboard.press(0, Action::Up).unwrap();
+        take(&sent);
+
+        keyboard.release(0, Action::Up).unwrap();
+        keyboard.press(0, Action::Up).unwrap();
+        assert!(take(&sent).is_empty());
+    }
+
+    #[test]
+    fn repeated_presses_and_releases_count_once() {
+        let (mut keyboard, sent) = keyboard(&Config::default(), None);
+        let a = key("a");
+
+        keyboard.press(0, Action::Green).unwrap();
+        keyboard.press(0, Action::Green).unwrap();
+        keyboard.release(0, Action::Green).unwrap();
+        keyboard.release(0, Action::Green).unwrap();
+        assert_eq!(take(&sent), [(a, Direction::Press), (a, Direction::Release)]);
+        assert!(keyboard.key_counts.is_empty());
+    }
+
+    #[test]
+    fn failed_press_rolls_back_the_chord() {
+        let config = config(&[("green", "Ctrl+1"), ("red", "Ctrl+2")]);
+        let (mut keyboard, sent) = keyboard(&config, Some(key("2")));
+        let (ctrl, one) = (key("Ctrl"), key("1"));
+
+        keyboard.press(0, Action::Green).unwrap();
+        assert!(keyboard.press(0, Action::Red).is_err());
+        // Ctrl stays down for green
+        assert_eq!(take(&sent), [(ctrl, Direction::Press), (one, Direction::Press)]);
+
+        keyboard.release(0, Action::Green).unwrap();
+        assert_eq!(take(&sent), [(one, Direction::Release), (ctrl, Direction::Release)]);
+        assert!(keyboard.key_counts.is_empty());
+    }
+
+    #[test]
+    fn release_all_only_lets_go_of_that_player() {
+        let (mut keyboard, sent) = keyboard(&Config::default(), None);
+        keyboard.press(0, Action::Green).unwrap();
+        keyboard.press(1, Action::Red).unwrap();
+        take(&sent);
+
+        keyboard.release_all(0);
+        assert_eq!(take(&sent), [(key("a"), Direction::Release)]);
+        assert!(keyboard.held.contains(&(1, Action::Red)));
+    }
+
+    #[test]
+    fn configure_releases_with_the_old_bindings() {
+        let (mut keyboard, sent) = keyboard(&Config::default(), None);
+        keyboard.press(0, Action::Green).unwrap();
+        keyboard.axis(0, Action::Whammy, 1.0).unwrap();
+        take(&sent);
+
+        keyboard.configure(&config(&[("green", "z"), ("whammy", "x")]));
+        let mut released = take(&sent);
+        released.sort_by_key(|&(key, _)| format!("{:?}", key));
+        assert_eq!(
+            released,
+            [(key(";"), Direction::Release), (key("a"), Direction::Release)]
+        );
+        assert!(keyboard.held.is_empty() && keyboard.key_counts.is_empty());
+
+        // Releasing after the rebind doesn't send the new key up
+        keyboard.release(0, Action::Green).unwrap();
+        assert!(take(&sent).is_empty());
+        keyboard.press(0, Action::Green).unwrap();
+        assert_eq!(take(&sent), [(key("z"), Direction::Press)]);
+    }
 }
@@ -0,0 +1,490 @@
+use serde::Serialize;
+
+// A key on the user's keyboard, independent of the OS. The output backend
+// decides how to send it (enigo keys, keysyms, virtual key codes, ...).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KeyCode {
+    // Letters, digits and punctuation, typed as their character
+    Char(char),
+    // The key that types this character on a US QWERTY keyboard, whatever
+    // the PC's layout is (bound as "phys:a")
+    Physical(char),
+    // F1 to F24
+    F(u8),
+    Shift,
+    LShift,
+    RShift,
+    Control,
+    LControl,
+    RControl,
+    Alt,
+    RAlt,
+    Meta,
+    Up,
+    Down,
+    Left,
+    Right,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    Space,
+    Enter,
+    Escape,
+    Tab,
+    Backspace,
+    Delete,
+    Insert,
+    CapsLock,
+    // Numpad 0 to 9
+    Numpad(u8),
+    NumpadAdd,
+    NumpadSubtract,
+    NumpadMultiply,
+    NumpadDivide,
+    NumpadDecimal,
+    NumpadEnter,
+    MediaPlayPause,
+    MediaNextTrack,
+    MediaPrevTrack,
+    MediaStop,
+    VolumeUp,
+    VolumeDown,
+    VolumeMute,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyCategory {
+    Letter,
+    Digit,
+    Function,
+    Modifier,
+    Navigation,
+    Editing,
+    Punctuation,
+    Numpad,
+    Media,
+}
+
+impl KeyCategory {
+    pub const ALL: [KeyCategory; 9] = [
+        KeyCategory::Letter,
+        KeyCategory::Digit,
+        KeyCategory::Function,
+        KeyCategory::Modifier,
+        KeyCategory::Navigation,
+        KeyCategory::Editing,
+        KeyCategory::Punctuation,
+        KeyCategory::Numpad,
+        KeyCategory::Media,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            KeyCategory::Letter => "Letters",
+            KeyCategory::Digit => "Digits",
+            KeyCategory::Function => "Function Keys",
+            KeyCategory::Modifier => "Modifiers",
+            KeyCategory::Navigation => "Navigation",
+            KeyCategory::Editing => "Editing",
+            KeyCategory::Punctuation => "Punctuation",
+            KeyCategory::Numpad => "Numpad",
+            KeyCategory::Media => "Media",
+        }
+    }
+}
+
+// One entry of the catalog. `name` is what the settings UI writes into
+// `Config.key_bindings`; aliases cover other spellings, including the
+// browser's `KeyboardEvent.key`/`code` names ("ArrowUp", "ShiftRight").
+#[derive(Debug, Clone, Serialize)]
+pub struct KeyName {
+    pub name: &'static str,
+    pub aliases: &'static [&'static str],
+    pub category: KeyCategory,
+    #[serde(skip)]
+    pub code: KeyCode,
+}
+
+const fn key(
+    name: &'static str,
+    aliases: &'static [&'static str],
+    category: KeyCategory,
+    code: KeyCode,
+) -> KeyName {
+    KeyName { name, aliases, category, code }
+}
+
+// Every key name a binding may use. Matching ignores case.
+pub const KEYS: &[KeyName] = &[
+    key("a", &[], KeyCategory::Letter, KeyCode::Char('a')),
+    key("b", &[], KeyCategory::Letter, KeyCode::Char('b')),
+    key("c", &[], KeyCategory::Letter, KeyCode::Char('c')),
+    key("d", &[], KeyCategory::Letter, KeyCode::Char('d')),
+    key("e", &[], KeyCategory::Letter, KeyCode::Char('e')),
+    key("f", &[], KeyCategory::Letter, KeyCode::Char('f')),
+    key("g", &[], KeyCategory::Letter, KeyCode::Char('g')),
+    key("h", &[], KeyCategory::Letter, KeyCode::Char('h')),
+    key("i", &[], KeyCategory::Letter, KeyCode::Char('i')),
+    key("j", &[], KeyCategory::Letter, KeyCode::Char('j')),
+    key("k", &[], KeyCategory::Letter, KeyCode::Char('k')),
+    key("l", &[], KeyCategory::Letter, KeyCode::Char('l')),
+    key("m", &[], KeyCategory::Letter, KeyCode::Char('m')),
+    key("n", &[], KeyCategory::Letter, KeyCode::Char('n')),
+    key("o", &[], KeyCategory::Letter, KeyCode::Char('o')),
+    key("p", &[], KeyCategory::Letter, KeyCode::Char('p')),
+    key("q", &[], KeyCategory::Letter, KeyCode::Char('q')),
+    key("r", &[], KeyCategory::Letter, KeyCode::Char('r')),
+    key("s", &[], KeyCategory::Letter, KeyCode::Char('s')),
+    key("t", &[], KeyCategory::Letter, KeyCode::Char('t')),
+    key("u", &[], KeyCategory::Letter, KeyCode::Char('u')),
+    key("v", &[], KeyCategory::Letter, KeyCode::Char('v')),
+    key("w", &[], KeyCategory::Letter, KeyCode::Char('w')),
+    key("x", &[], KeyCategory::Letter, KeyCode::Char('x')),
+    key("y", &[], KeyCategory::Letter, KeyCode::Char('y')),
+    key("z", &[], KeyCategory::Letter, KeyCode::Char('z')),
+    key("0", &["digit0"], KeyCategory::Digit, KeyCode::Char('0')),
+    key("1", &["digit1"], KeyCategory::Digit, KeyCode::Char('1')),
+    key("2", &["digit2"], KeyCategory::Digit, KeyCode::Char('2')),
+    key("3", &["digit3"], KeyCategory::Digit, KeyCode::Char('3')),
+    key("4", &["digit4"], KeyCategory::Digit, KeyCode::Char('4')),
+    key("5", &["digit5"], KeyCategory::Digit, KeyCode::Char('5')),
+    key("6", &["digit6"], KeyCategory::Digit, KeyCode::Char('6')),
+    key("7", &["digit7"], KeyCategory::Digit, KeyCode::Char('7')),
+    key("8", &["digit8"], KeyCategory::Digit, KeyCode::Char('8')),
+    key("9", &["digit9"], KeyCategory::Digit, KeyCode::Char('9')),
+    key("F1", &[], KeyCategory::Function, KeyCode::F(1)),
+    key("F2", &[], KeyCategory::Function, KeyCode::F(2)),
+    key("F3", &[], KeyCategory::Function, KeyCode::F(3)),
+    key("F4", &[], KeyCategory::Function, KeyCode::F(4)),
+    key("F5", &[], KeyCategory::Function, KeyCode::F(5)),
+    key("F6", &[], KeyCategory::Function, KeyCode::F(6)),
+    key("F7", &[], KeyCategory::Function, KeyCode::F(7)),
+    key("F8", &[], KeyCategory::Function, KeyCode::F(8)),
+    key("F9", &[], KeyCategory::Function, KeyCode::F(9)),
+    key("F10", &[], KeyCategory::Function, KeyCode::F(10)),
+    key("F11", &[], KeyCategory::Function, KeyCode::F(11)),
+    key("F12", &[], KeyCategory::Function, KeyCode::F(12)),
+    key("F13", &[], KeyCategory::Function, KeyCode::F(13)),
+    key("F14", &[], KeyCategory::Function, KeyCode::F(14)),
+    key("F15", &[], KeyCategory::Function, KeyCode::F(15)),
+    key("F16", &[], KeyCategory::Function, KeyCode::F(16)),
+    key("F17", &[], KeyCategory::Function, KeyCode::F(17)),
+    key("F18", &[], KeyCategory::Function, KeyCode::F(18)),
+    key("F19", &[], KeyCategory::Function, KeyCode::F(19)),
+    key("F20", &[], KeyCategory::Function, KeyCode::F(20)),
+    key("F21", &[], KeyCategory::Function, KeyCode::F(21)),
+    key("F22", &[], KeyCategory::Function, KeyCode::F(22)),
+    key("F23", &[], KeyCategory::Function, KeyCode::F(23)),
+    key("F24", &[], KeyCategory::Function, KeyCode::F(24)),
+    key("Shift", &[], KeyCategory::Modifier, KeyCode::Shift),
+    key("LShift", &["ShiftLeft", "LeftShift"], KeyCategory::Modifier, KeyCode::LShift),
+    key("RShift", &["ShiftRight", "RightShift"], KeyCategory::Modifier, KeyCode::RShift),
+    key("Ctrl", &["Control"], KeyCategory::Modifier, KeyCode::Control),
+    key("LCtrl", &["ControlLeft", "LeftCtrl", "LControl"], KeyCategory::Modifier, KeyCode::LControl),
+    key("RCtrl", &["ControlRight", "RightCtrl", "RControl"], KeyCategory::Modifier, KeyCode::RControl),
+    key("Alt", &["LAlt", "AltLeft", "Option"], KeyCategory::Modifier, KeyCode::Alt),
+    key("RAlt", &["AltRight", "AltGr", "AltGraph"], KeyCategory::Modifier, KeyCode::RAlt),
+    key("Meta", &["Win", "Windows", "Super", "Cmd", "Command", "OS", "MetaLeft", "MetaRight"], KeyCategory::Modifier, KeyCode::Meta),
+    key("Up", &["ArrowUp"], KeyCategory::Navigation, KeyCode::Up),
+    key("Down", &["ArrowDown"], KeyCategory::Navigation, KeyCode::Down),
+    key("Left", &["ArrowLeft"], KeyCategory::Navigation, KeyCode::Left),
+    key("Right", &["ArrowRight"], KeyCategory::Navigation, KeyCode::Right),
+    key("Home", &[], KeyCategory::Navigation, KeyCode::Home),
+    key("End", &[], KeyCategory::Navigation, KeyCode::End),
+    key("PageUp", &["PgUp"], KeyCategory::Navigation, KeyCode::PageUp),
+    key("PageDown", &["PgDn"], KeyCategory::Navigation, KeyCode::PageDown),
+    key("Space", &[" ", "Spacebar"], KeyCategory::Editing, KeyCode::Space),
+    key("Enter", &["Return"], KeyCategory::Editing, KeyCode::Enter),
+    key("Escape", &["Esc"], KeyCategory::Editing, KeyCode::Escape),
+    key("Tab", &[], KeyCategory::Editing, KeyCode::Tab),
+    key("Backspace", &[], KeyCategory::Editing, KeyCode::Backspace),
+    key("Delete", &["Del"], KeyCategory::Editing, KeyCode::Delete),
+    key("Insert", &["Ins"], KeyCategory::Editing, KeyCode::Insert),
+    key("CapsLock", &["Caps"], KeyCategory::Editing, KeyCode::CapsLock),
+    key(";", &["semicolon"], KeyCategory::Punctuation, KeyCode::Char(';')),
+    key("'", &["apostrophe", "quote"], KeyCategory::Punctuation, KeyCode::Char('\'')),
+    key(",", &["comma"], KeyCategory::Punctuation, KeyCode::Char(',')),
+    key(".", &["period"], KeyCategory::Punctuation, KeyCode::Char('.')),
+    key("/", &["slash"], KeyCategory::Punctuation, KeyCode::Char('/')),
+    key("-", &["minus"], KeyCategory::Punctuation, KeyCode::Char('-')),
+    key("=", &["equal", "equals"], KeyCategory::Punctuation, KeyCode::Char('=')),
+    key("[", &["bracketleft"], KeyCategory::Punctuation, KeyCode::Char('[')),
+    key("]", &["bracketright"], KeyCategory::Punctuation, KeyCode::Char(']')),
+    key("\\", &["backslash"], KeyCategory::Punctuation, KeyCode::Char('\\')),
+    key("`", &["backquote", "grave"], KeyCategory::Punctuation, KeyCode::Char('`')),
+    key("Numpad0", &["Num0", "Kp0"], KeyCategory::Numpad, KeyCode::Numpad(0)),
+    key("Numpad1", &["Num1", "Kp1"], KeyCategory::Numpad, KeyCode::Numpad(1)),
+    key("Numpad2", &["Num2", "Kp2"], KeyCategory::Numpad, KeyCode::Numpad(2)),
+    key("Numpad3", &["Num3", "Kp3"], KeyCategory::Numpad, KeyCode::Numpad(3)),
+    key("Numpad4", &["Num4", "Kp4"], KeyCategory::Numpad, KeyCode::Numpad(4)),
+    key("Numpad5", &["Num5", "Kp5"], KeyCategory::Numpad, KeyCode::Numpad(5)),
+    key("Numpad6", &["Num6", "Kp6"], KeyCategory::Numpad, KeyCode::Numpad(6)),
+    key("Numpad7", &["Num7", "Kp7"], KeyCategory::Numpad, KeyCode::Numpad(7)),
+    key("Numpad8", &["Num8", "Kp8"], KeyCategory::Numpad, KeyCode::Numpad(8)),
+    key("Numpad9", &["Num9", "Kp9"], KeyCategory::Numpad, KeyCode::Numpad(9)),
+    key("NumpadAdd", &["NumpadPlus", "KpAdd"], KeyCategory::Numpad, KeyCode::NumpadAdd),
+    key("NumpadSubtract", &["NumpadMinus", "KpSubtract"], KeyCategory::Numpad, KeyCode::NumpadSubtract),
+    key("NumpadMultiply", &["KpMultiply"], KeyCategory::Numpad, KeyCode::NumpadMultiply),
+    key("NumpadDivide", &["KpDivide"], KeyCategory::Numpad, KeyCode::NumpadDivide),
+    key("NumpadDecimal", &["KpDecimal"], KeyCategory::Numpad, KeyCode::NumpadDecimal),
+    key("NumpadEnter", &["KpEnter"], KeyCategory::Numpad, KeyCode::NumpadEnter),
+    key("MediaPlayPause", &["PlayPause"], KeyCategory::Media, KeyCode::MediaPlayPause),
+    key("MediaNextTrack", &["NextTrack"], KeyCategory::Media, KeyCode::MediaNextTrack),
+    key("MediaPrevTrack", &["PrevTrack", "MediaTrackPrevious"], KeyCategory::Media, KeyCode::MediaPrevTrack),
+    key("MediaStop", &[], KeyCategory::Media, KeyCode::MediaStop),
+    key("VolumeUp", &["AudioVolumeUp"], KeyCategory::Media, KeyCode::VolumeUp),
+    key("VolumeDown", &["AudioVolumeDown"], KeyCategory::Media, KeyCode::VolumeDown),
+    key("VolumeMute", &["Mute", "AudioVolumeMute"], KeyCategory::Media, KeyCode::VolumeMute),
+];
+
+// Marks a binding as a physical key position rather than a character
+pub const PHYSICAL_PREFIX: &str = "phys:";
+
+// Resolves a binding such as "a", "F5", "RShift", "Numpad0" or "phys:q".
+// Any other single character is typed as-is, as bindings always allowed.
+pub fn parse(name: &str) -> Option<KeyCode> {
+    parse_key(name, false)
+}
+
+fn parse_key(name: &str, physical: bool) -> Option<KeyCode> {
+    if let Some(rest) = strip_prefix_ignore_case(name, PHYSICAL_PREFIX) {
+        return parse_key(rest, true);
+    }
+    match parse_name(name)? {
+        KeyCode::Char(c) if physical => {
+            let c = c.to_ascii_lowercase();
+            scancode(c).map(|_| KeyCode::Physical(c))
+        }
+        // Named keys sit in the same place on every layout already
+        code => Some(code),
+    }
+}
+
+// Resolves a binding that may be a chord of keys pressed together, e.g.
+// "Ctrl+Shift+F1" or "a+s". They are pressed in order and released in
+// reverse. A "+" key is written as "+" alone or at the end ("Ctrl++").
+// A leading "phys:" applies to every key of the chord.
+pub fn parse_chord(binding: &str) -> Option<Vec<KeyCode>> {
+    let (binding, physical) = match strip_prefix_ignore_case(binding, PHYSICAL_PREFIX) {
+        Some(rest) => (rest, true),
+        None => (binding, false),
+    };
+    if binding.len() <= 1 {
+        return parse_key(binding, physical).map(|key| vec![key]);
+    }
+    let (rest, plus) = match binding.strip_suffix("++") {
+        Some(rest) => (rest, true),
+        None => (binding, false),
+    };
+    let mut chord = rest
+        .split('+')
+        .map(|part| parse_key(part.trim(), physical))
+        .collect::<Option<Vec<_>>>()?;
+    if plus {
+        chord.push(parse_key("+", physical)?);
+    }
+    Some(chord)
+}
+
+fn strip_prefix_ignore_case<'a>(name: &'a str, prefix: &str) -> Option<&'a str> {
+    let head = name.get(..prefix.len())?;
+    head.eq_ignore_ascii_case(prefix).then(|| &name[prefix.len()..])
+}
+
+fn parse_name(name: &str) -> Option<KeyCode> {
+    let entry = KEYS.iter().find(|key| {
+        key.name.eq_ignore_ascii_case(name) || key.aliases.iter().any(|alias| alias.eq_ignore_ascii_case(name))
+    });
+    if let Some(entry) = entry {
+        return Some(entry.code);
+    }
+
+    let mut chars = name.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(KeyCode::Char(c)),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct KeyGroup {
+    pub category: KeyCategory,
+    pub label: &'static str,
+    pub keys: Vec<&'static KeyName>,
+}
+
+// The catalog grouped by category, in display order
+pub fn catalog() -> Vec<KeyGroup> {
+    KeyCategory::ALL
+        .iter()
+        .map(|&category| KeyGroup {
+            category,
+            label: category.label(),
+            keys: KEYS.iter().filter(|key| key.category == category).collect(),
+        })
+        .collect()
+}
+
+// PC/AT (set 1) scan code of the key typing `c` on US QWERTY. Linux evdev
+// key codes are the same numbers for these keys.
+pub fn scancode(c: char) -> Option<u16> {
+    let code = match c {
+        '1' => 2,
+        '2' => 3,
+        '3' => 4,
+        '4' => 5,
+        '5' => 6,
+        '6' => 7,
+        '7' => 8,
+        '8' => 9,
+        '9' => 10,
+        '0' => 11,
+        '-' => 12,
+        '=' => 13,
+        'q' => 16,
+        'w' => 17,
+        'e' => 18,
+        'r' => 19,
+        't' => 20,
+        'y' => 21,
+        'u' => 22,
+        'i' => 23,
+        'o' => 24,
+        'p' => 25,
+        '[' => 26,
+        ']' => 27,
+        'a' => 30,
+        's' => 31,
+        'd' => 32,
+        'f' => 33,
+        'g' => 34,
+        'h' => 35,
+        'j' => 36,
+        'k' => 37,
+        'l' => 38,
+        ';' => 39,
+        '\'' => 40,
+        '`' => 41,
+        '\\' => 43,
+        'z' => 44,
+        'x' => 45,
+        'c' => 46,
+        'v' => 47,
+        'b' => 48,
+        'n' => 49,
+        'm' => 50,
+        ',' => 51,
+        '.' => 52,
+        '/' => 53,
+        _ => return None,
+    };
+    Some(code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_names_and_aliases_ignoring_case() {
+        assert_eq!(parse("a"), Some(KeyCode::Char('a')));
+        assert_eq!(parse("A"), Some(KeyCode::Char('a')));
+        assert_eq!(parse("F1"), Some(KeyCode::F(1)));
+        assert_eq!(parse("f24"), Some(KeyCode::F(24)));
+        assert_eq!(parse("ctrl"), Some(KeyCode::Control));
+        assert_eq!(parse("CONTROL"), Some(KeyCode::Control));
+        assert_eq!(parse("ArrowUp"), Some(KeyCode::Up));
+        assert_eq!(parse("kp0"), Some(KeyCode::Numpad(0)));
+        assert_eq!(parse(" "), Some(KeyCode::Space));
+        assert_eq!(parse("semicolon"), Some(KeyCode::Char(';')));
+        // Any other single character is typed as is
+        assert_eq!(parse("+"), Some(KeyCode::Char('+')));
+        assert_eq!(parse("é"), Some(KeyCode::Char('é')));
+    }
+
+    #[test]
+    fn rejects_unknown_names() {
+        for name in ["", "foo", "F25", "Ctrl+", "ab", "phys:"] {
+            assert_eq!(parse(name), None, "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn physical_prefix() {
+        assert_eq!(parse("phys:q"), Some(KeyCode::Physical('q')));
+        assert_eq!(parse("PHYS:Q"), Some(KeyCode::Physical('q')));
+        assert_eq!(parse("phys:semicolon"), Some(KeyCode::Physical(';')));
+        // Named keys don't move between layouts
+        assert_eq!(parse("phys:F5"), Some(KeyCode::F(5)));
+        // Only keys with a US QWERTY position
+        assert_eq!(parse("phys:é"), None);
+        assert_eq!(parse("phys:+"), None);
+    }
+
+    #[test]
+    fn chords() {
+        use KeyCode::*;
+        let cases: &[(&str, &[KeyCode])] = &[
+            ("a", &[Char('a')]),
+            ("Ctrl+Shift+F1", &[Control, Shift, F(1)]),
+            ("a+s", &[Char('a'), Char('s')]),
+            (" ctrl + a ", &[Control, Char('a')]),
+            ("+", &[Char('+')]),
+            ("Ctrl++", &[Control, Char('+')]),
+            ("Ctrl+Shift++", &[Control, Shift, Char('+')]),
+            ("phys:ctrl+q", &[Control, Physical('q')]),
+            ("phys:q+w", &[Physical('q'), Physical('w')]),
+            (" ", &[Space]),
+        ];
+        for &(binding, expected) in cases {
+            assert_eq!(parse_chord(binding).as_deref(), Some(expected), "{:?}", binding);
+        }
+    }
+
+    #[test]
+    fn rejects_bad_chords() {
+        for binding in ["", "a+", "+a", "a++b", "Ctrl+foo", "Ctrl+ +a", "phys:", "phys:Ctrl++", "phys:a+é"] {
+            assert_eq!(parse_chord(binding), None, "{:?}", binding);
+        }
+    }
+
+    #[test]
+    fn scancodes() {
+        assert_eq!(scancode('1'), Some(2));
+        assert_eq!(scancode('q'), Some(16));
+        assert_eq!(scancode('a'), Some(30));
+        assert_eq!(scancode('z'), Some(44));
+        assert_eq!(scancode('/'), Some(53));
+        // Callers lowercase first; shifted characters have no key of their own
+        assert_eq!(scancode('Q'), None);
+        assert_eq!(scancode('+'), None);
+        // Every letter and digit has one
+        assert!(('a'..='z').chain('0'..='9').all(|c| scancode(c).is_some()));
+    }
+
+    #[test]
+    fn catalog_lists_every_key_once_in_category_order() {
+        let groups = catalog();
+        let categories: Vec<_> = groups.iter().map(|g| g.category).collect();
+        assert_eq!(categories, KeyCategory::ALL);
+        let listed: usize = groups.iter().map(|g| g.keys.len()).sum();
+        assert_eq!(listed, KEYS.len());
+        for group in &groups {
+            assert_eq!(group.label, group.category.label());
+            assert!(group.keys.iter().all(|k| k.category == group.category));
+        }
+    }
+
+    #[test]
+    fn catalog_names_resolve_to_their_key() {
+        let mut seen = std::collections::HashSet::new();
+        for key in KEYS {
+            for name in std::iter::once(&key.name).chain(key.aliases) {
+                assert!(seen.insert(name.to_lowercase()), "{} listed twice", name);
+                assert_eq!(parse(name), Some(key.code), "{}", name);
+            }
+        }
+    }
+}
@@ -0,0 +1,405 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+
+use crate::bindings::Action;
+use crate::config::Config;
+use crate::events::EventSink;
+use crate::injector::Injector;
+use crate::watchdog::HoldWatchdog;
+
+// How long a "tap" holds its key; games poll input once a frame
+const TAP_HOLD: Duration = Duration::from_millis(40);
+
+// One step of a macro in `Config.macros`, written as {"tap": "down"},
+// {"press": "start"}, {"release": "start"} or {"wait": 150} (milliseconds).
+// Steps name actions, so macros work with every output backend.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MacroStep {
+    Press(String),
+    Release(String),
+    Tap(String),
+    Wait(u64),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Step {
+    Press(Action),
+    Release(Action),
+    Tap(Action),
+    Wait(Duration),
+}
+
+fn compile(steps: &[MacroStep]) -> Result<Vec<Step>, String> {
+    let action = |name: &str| Action::from_name(name).ok_or_else(|| format!("unknown action \"{}\"", name));
+    steps
+        .iter()
+        .map(|step| {
+            Ok(match step {
+                MacroStep::Press(name) => Step::Press(action(name)?),
+                MacroStep::Release(name) => Step::Release(action(name)?),
+                MacroStep::Tap(name) => Step::Tap(action(name)?),
+                MacroStep::Wait(ms) => Step::Wait(Duration::from_millis(*ms)),
+            })
+        })
+        .collect()
+}
+
+// What a connection's client and its macro hold. Both press through the
+// same (player, action) slot of the output, so neither side sends a release
+// while the other still holds the action.
+struct Holders {
+    client: HashSet<Action>,
+    // A macro can press and then wait as long as it likes, so its holds get
+    // a watchdog of their own
+    by_macro: HoldWatchdog,
+    // Bumped when a macro is cancelled, so its task winding down can't touch
+    // the holds of the next one
+    run: u64,
+}
+
+impl Holders {
+    fn release_macro(&mut self, output: &Injector, player: u8) {
+        let held: Vec<Action> = self.by_macro.held(Instant::now()).map(|(action, _)| action).collect();
+        for action in held {
+            self.by_macro.released(action);
+            if !self.client.contains(&action) {
+                output.release(player, action);
+            }
+        }
+    }
+}
+
+// One macro run's presses. Whatever it still holds is let go when this
+// drops at the end of the macro.
+struct MacroKeys {
+    output: Injector,
+    player: u8,
+    holders: Arc<Mutex<Holders>>,
+    run: u64,
+}
+
+impl MacroKeys {
+    // None once the run was cancelled
+    fn holders(&self) -> Option<MutexGuard<'_, Holders>> {
+        let holders = self.holders.lock().unwrap();
+        (holders.run == self.run).then_some(holders)
+    }
+
+    fn press(&self, action: Action) {
+        let Some(mut holders) = self.holders() else {
+            return;
+        };
+        let down = holders.client.contains(&action) || holders.by_macro.holds(action);
+        holders.by_macro.pressed(action, Instant::now());
+        if !down {
+            self.output.press(self.player, action);
+        }
+    }
+
+    // Only lets go of what the macro pressed
+    fn release(&self, action: Action) {
+        let Some(mut holders) = self.holders() else {
+            return;
+        };
+        if !holders.by_macro.holds(action) {
+            return;
+        }
+        holders.by_macro.released(action);
+        if !holders.client.contains(&action) {
+            self.output.release(self.player, action);
+        }
+    }
+}
+
+impl Drop for MacroKeys {
+    fn drop(&mut self) {
+        if let Some(mut holders) = self.holders() {
+            holders.release_macro(&self.output, self.player);
+        }
+    }
+}
+
+// Runs one connection's macros, at most one at a time. Starting another one
+// or dropping the runner (on disconnect) cancels the current one. The
+// client's presses and releases go through it too, so the two don't let go
+// of each other's actions.
+pub struct MacroRunner {
+    output: Injector,
+    player: u8,
+    sink: Arc<dyn EventSink>,
+    holders: Arc<Mutex<Holders>>,
+    running: Option<(String, JoinHandle<()>)>,
+}
+
+impl MacroRunner {
+    pub fn new(output: Injector, player: u8, config: &Config, sink: Arc<dyn EventSink>) -> Self {
+        let holders = Holders {
+            client: HashSet::new(),
+            by_macro: HoldWatchdog::new(config),
+            run: 0,
+        };
+        Self {
+            output,
+            player,
+            sink,
+            holders: Arc::new(Mutex::new(holders)),
+            running: None,
+        }
+    }
+
+    // Not sent on while the macro holds the action
+    pub fn client_press(&self, action: Action) {
+        let mut holders = self.holders.lock().unwrap();
+        holders.client.insert(action);
+        if !holders.by_macro.holds(action) {
+            self.output.press(self.player, action);
+        }
+    }
+
+    pub fn client_release(&self, action: Action) {
+        let mut holders = self.holders.lock().unwrap();
+        holders.client.remove(&action);
+        if !holders.by_macro.holds(action) {
+            self.output.release(self.player, action);
+        }
+    }
+
+    // Lets go of macro holds past their limit, returning them with how long
+    // they were held
+    pub fn expired(&self, now: Instant) -> Vec<(Action, Duration)> {
+        let mut holders = self.holders.lock().unwrap();
+        let expired = holders.by_macro.expired(now);
+        for &(action, _) in &expired {
+            if !holders.client.contains(&action) {
+                self.output.release(self.player, action);
+            }
+        }
+        expired
+    }
+
+    pub fn start(&mut self, name: &str, config: &Config) -> Result<(), String> {
+        let steps = config
+            .macros
+            .get(name)
+            .ok_or_else(|| format!("No macro named \"{}\"", name))?;
+        let steps = compile(steps).map_err(|e| format!("Macro \"{}\": {}", name, e))?;
+        self.cancel();
+
+        let keys = MacroKeys {
+            output: self.output.clone(),
+            player: self.player,
+            holders: Arc::clone(&self.holders),
+            run: self.holders.lock().unwrap().run,
+        };
+        let sink = Arc::clone(&self.sink);
+        let macro_name = name.to_string();
+        let task = tokio::spawn(async move {
+            for step in steps {
+                match step {
+                    Step::Press(action) => keys.press(action),
+                    Step::Release(action) => keys.release(action),
+                    Step::Tap(action) => {
+                        keys.press(action);
+                        tokio::time::sleep(TAP_HOLD).await;
+                        keys.release(action);
+                    }
+                    Step::Wait(duration) => tokio::time::sleep(duration).await,
+                }
+            }
+            drop(keys);
+            sink.log(format!("[MACRO] {} finished", macro_name));
+        });
+
+        self.sink.log(format!("[MACRO] {} started", name));
+        self.running = Some((name.to_string(), task));
+        Ok(())
+    }
+
+    // Stops the running macro, releasing its keys. False if none was running.
+    pub fn cancel(&mut self) -> bool {
+        let Some((name, task)) = self.running.take() else {
+            return false;
+        };
+        // Released here rather than when the aborted task drops, which can
+        // be after the next macro has started
+        {
+            let mut holders = self.holders.lock().unwrap();
+            holders.run += 1;
+            holders.release_macro(&self.output, self.player);
+        }
+        if task.is_finished() {
+            return false;
+        }
+        task.abort();
+        self.sink.log(format!("[MACRO] {} cancelled", name));
+        true
+    }
+}
+
+impl Drop for MacroRunner {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::RecordingSink;
+    use crate::output::{OutputEvent, RecordingOutput};
+
+    const PLAYER: u8 = 2;
+    // Connecting this slot marks how far the injector has got
+    const MARK: u8 = 7;
+
+    struct Harness {
+        recording: RecordingOutput,
+        output: Injector,
+        config: Config,
+    }
+
+    fn harness(name: &str, steps: Vec<MacroStep>) -> (Harness, MacroRunner) {
+        let mut config = Config::default();
+        config.macros.insert(name.to_string(), steps);
+        let recording = RecordingOutput::new();
+        let output = Injector::spawn(Box::new(recording.clone()), Arc::new(RecordingSink::new()));
+        let runner = MacroRunner::new(output.clone(), PLAYER, &config, Arc::new(RecordingSink::new()));
+        (Harness { recording, output, config }, runner)
+    }
+
+    impl Harness {
+        // Every event sent so far, once the injector has delivered them
+        async fn events(&self) -> Vec<OutputEvent> {
+            self.output.connect(MARK);
+            for _ in 0..200 {
+                let events = self.recording.events();
+                if events.last() == Some(&OutputEvent::Connect(MARK)) {
+                    return events.into_iter().filter(|e| *e != OutputEvent::Connect(MARK)).collect();
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            panic!("injector did not catch up");
+        }
+
+        async fn wait_for(&self, event: OutputEvent) {
+            for _ in 0..200 {
+                if self.recording.events().contains(&event) {
+                    return;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            panic!("{:?} never sent", event);
+        }
+    }
+
+    fn press(action: &str) -> MacroStep {
+        MacroStep::Press(action.to_string())
+    }
+
+    fn tap(action: &str) -> MacroStep {
+        MacroStep::Tap(action.to_string())
+    }
+
+    #[test]
+    fn rejects_unknown_actions() {
+        assert!(compile(&[tap("down"), MacroStep::Wait(150), press("start")]).is_ok());
+        assert_eq!(compile(&[tap("sideways")]).unwrap_err(), "unknown action \"sideways\"");
+    }
+
+    #[tokio::test]
+    async fn cancel_releases_only_what_the_macro_pressed() {
+        let (h, mut runner) = harness("hold", vec![tap("green"), press("red"), MacroStep::Wait(10_000)]);
+
+        runner.client_press(Action::Green);
+        runner.start("hold", &h.config).unwrap();
+        h.wait_for(OutputEvent::Press(PLAYER, Action::Red)).await;
+        assert!(runner.cancel());
+        // The tap of green, which the client holds, goes nowhere
+        assert_eq!(
+            h.events().await,
+            [
+                OutputEvent::Press(PLAYER, Action::Green),
+                OutputEvent::Press(PLAYER, Action::Red),
+                OutputEvent::Release(PLAYER, Action::Red),
+            ]
+        );
+
+        runner.client_release(Action::Green);
+        assert_eq!(h.events().await.last(), Some(&OutputEvent::Release(PLAYER, Action::Green)));
+    }
+
+    #[tokio::test]
+    async fn disconnect_releases_only_what_the_macro_pressed() {
+        let (h, mut runner) = harness("hold", vec![press("start"), press("orange"), MacroStep::Wait(10_000)]);
+
+        runner.client_press(Action::Orange);
+        runner.start("hold", &h.config).unwrap();
+        h.wait_for(OutputEvent::Press(PLAYER, Action::Start)).await;
+        drop(runner);
+        assert_eq!(
+            h.events().await,
+            [
+                OutputEvent::Press(PLAYER, Action::Orange),
+                OutputEvent::Press(PLAYER, Action::Start),
+                OutputEvent::Release(PLAYER, Action::Start),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn client_release_waits_for_the_macro() {
+        let (h, mut runner) = harness("hold", vec![press("start"), MacroStep::Wait(10_000)]);
+
+        runner.start("hold", &h.config).unwrap();
+        h.wait_for(OutputEvent::Press(PLAYER, Action::Start)).await;
+        runner.client_press(Action::Start);
+        runner.client_release(Action::Start);
+        assert_eq!(h.events().await, [OutputEvent::Press(PLAYER, Action::Start)]);
+
+        runner.cancel();
+        assert_eq!(h.events().await.last(), Some(&OutputEvent::Release(PLAYER, Action::Start)));
+    }
+
+    #[tokio::test]
+    async fn finished_macro_lets_go_of_its_presses() {
+        let (h, mut runner) = harness("quick", vec![press("select"), tap("down")]);
+
+        runner.start("quick", &h.config).unwrap();
+        h.wait_for(OutputEvent::Release(PLAYER, Action::Select)).await;
+        assert_eq!(
+            h.events().await,
+            [
+                OutputEvent::Press(PLAYER, Action::Select),
+                OutputEvent::Press(PLAYER, Action::Down),
+                OutputEvent::Release(PLAYER, Action::Down),
+                OutputEvent::Release(PLAYER, Action::Select),
+            ]
+        );
+        assert!(!runner.cancel());
+    }
+
+    #[tokio::test]
+    async fn watchdog_releases_stuck_macro_holds() {
+        let (h, mut runner) = harness("strum", vec![press("strum_down"), MacroStep::Wait(10_000)]);
+
+        runner.start("strum", &h.config).unwrap();
+        h.wait_for(OutputEvent::Press(PLAYER, Action::StrumDown)).await;
+        assert!(runner.expired(Instant::now()).is_empty());
+        let expired = runner.expired(Instant::now() + Duration::from_secs(2));
+        assert_eq!(expired.iter().map(|&(action, _)| action).collect::<Vec<_>>(), [Action::StrumDown]);
+
+        // Cancelling afterwards doesn't release it twice
+        runner.cancel();
+        assert_eq!(
+            h.events().await,
+            [
+                OutputEvent::Press(PLAYER, Action::StrumDown),
+                OutputEvent::Release(PLAYER, Action::StrumDown),
+            ]
+        );
+    }
+}
@@ -0,0 +1,233 @@
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use crate::config::Config;
+
+// An address range like "192.168.0.0/16" or "fe80::/10". A bare address is
+// treated as a single host.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cidr {
+    network: IpAddr,
+    prefix: u8,
+}
+
+impl Cidr {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.network, ip.to_canonical()) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+                u32::from(net) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+                u128::from(net) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for Cidr {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (addr, prefix) = match s.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (s, None),
+        };
+
+        let network = IpAddr::from_str(addr)
+            .map_err(|_| format!("invalid address: {}", addr))?
+            .to_canonical();
+        let max = if network.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            // u8::from_str would also take a leading '+'
+            Some(p) => Some(p)
+                .filter(|p| !p.is_empty() && p.bytes().all(|b| b.is_ascii_digit()))
+                .and_then(|p| p.parse::<u8>().ok())
+                .filter(|p| *p <= max)
+                .ok_or_else(|| format!("invalid prefix length: {}", p))?,
+            None => max,
+        };
+
+        Ok(Self { network, prefix })
+    }
+}
+
+// Decides which remote addresses may connect. Deny rules win over allow
+// rules; an empty allowlist allows everything not denied.
+#[derive(Debug, Clone, Default)]
+pub struct NetworkFilter {
+    allow: Vec<Cidr>,
+    deny: Vec<Cidr>,
+}
+
+impl NetworkFilter {
+    // Builds the filter from config, returning any rules that failed to parse
+    // so the caller can report them.
+    pub fn from_config(config: &Config) -> (Self, Vec<String>) {
+        let mut errors = Vec::new();
+        let mut parse = |rules: &[String]| -> Vec<Cidr> {
+            rules
+                .iter()
+                .filter(|rule| !rule.trim().is_empty())
+                .filter_map(|rule| match rule.parse() {
+                    Ok(cidr) => Some(cidr),
+                    Err(e) => {
+                        errors.push(format!("{} ({})", rule, e));
+                        None
+                    }
+                })
+                .collect()
+        };
+
+        let filter = Self {
+            allow: parse(&config.allowed_networks),
+            deny: parse(&config.denied_networks),
+        };
+        (filter, errors)
+    }
+
+    pub fn permits(&self, ip: IpAddr) -> bool {
+        if self.deny.iter().any(|c| c.contains(ip)) {
+            return false;
+        }
+        self.allow.is_empty() || self.allow.iter().any(|c| c.contains(ip))
+    }
+}
+
+// Private (RFC 1918 / ULA), link-local and loopback ranges
+pub fn default_allowed_networks() -> Vec<String> {
+    [
+        "127.0.0.0/8",
+        "10.0.0.0/8",
+        "172.16.0.0/12",
+        "192.168.0.0/16",
+        "169.254.0.0/16",
+        "::1/128",
+        "fc00::/7",
+        "fe80::/10",
+    ]
+    .iter()
+    .map(|s| s.to_string())
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    fn filter(allowed: &[&str], denied: &[&str]) -> NetworkFilter {
+        let config = Config {
+            allowed_networks: allowed.iter().map(|s| s.to_string()).collect(),
+            denied_networks: denied.iter().map(|s| s.to_string()).collect(),
+            ..Default::default()
+        };
+        let (filter, errors) = NetworkFilter::from_config(&config);
+        assert!(errors.is_empty(), "{:?}", errors);
+        filter
+    }
+
+    #[test]
+    fn cidr_contains() {
+        let cases = [
+            ("192.168.0.0/16", "192.168.1.20", true),
+            ("192.168.0.0/16", "192.169.0.1", false),
+            ("10.0.0.0/8", "10.255.255.255", true),
+            ("172.16.0.0/12", "172.31.0.1", true),
+            ("172.16.0.0/12", "172.32.0.1", false),
+            ("0.0.0.0/0", "8.8.8.8", true),
+            ("10.0.0.5/32", "10.0.0.5", true),
+            ("10.0.0.5/32", "10.0.0.6", false),
+            ("10.0.0.5", "10.0.0.5", true),
+            ("10.0.0.5", "10.0.0.4", false),
+            ("fe80::/10", "fe80::1", true),
+            ("fe80::/10", "fec0::1", false),
+            ("2001:db8::/32", "2001:db8:ffff::1", true),
+            ("::/0", "2001:db8::1", true),
+            ("::1/128", "::1", true),
+            ("::1/128", "::2", false),
+            // IPv4-mapped peers (dual-stack sockets) match IPv4 ranges
+            ("192.168.0.0/16", "::ffff:192.168.1.20", true),
+            ("10.0.0.0/8", "::ffff:192.168.1.20", false),
+            ("::ffff:10.0.0.1", "10.0.0.1", true),
+            // Families never match each other
+            ("0.0.0.0/0", "2001:db8::1", false),
+            ("::/0", "10.0.0.1", false),
+        ];
+        for (cidr, addr, expected) in cases {
+            let parsed: Cidr = cidr.parse().unwrap();
+            assert_eq!(parsed.contains(ip(addr)), expected, "{} contains {}", cidr, addr);
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_cidrs() {
+        for cidr in [
+            "",
+            "abc",
+            "10.0.0/8",
+            "10.0.0.0/",
+            "10.0.0.0/33",
+            "10.0.0.0/-1",
+            "10.0.0.0/+8",
+            "10.0.0.0/8/8",
+            "10.0.0.0/ 8",
+            "::/129",
+            "fe80::1%eth0/64",
+        ] {
+            assert!(cidr.parse::<Cidr>().is_err(), "{:?} parsed", cidr);
+        }
+        assert_eq!(" 10.0.0.0/8 ".parse::<Cidr>(), "10.0.0.0/8".parse());
+    }
+
+    #[test]
+    fn invalid_rules_are_reported() {
+        let config = Config {
+            allowed_networks: vec!["10.0.0.0/8".to_string(), "nonsense".to_string(), " ".to_string()],
+            denied_networks: vec!["10.0.0.0/40".to_string()],
+            ..Default::default()
+        };
+        let (filter, errors) = NetworkFilter::from_config(&config);
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].starts_with("nonsense"));
+        assert!(filter.permits(ip("10.1.2.3")));
+        assert!(!filter.permits(ip("192.168.1.1")));
+    }
+
+    #[test]
+    fn deny_wins_over_allow() {
+        let filter = filter(&["192.168.0.0/16"], &["192.168.1.0/24"]);
+        assert!(filter.permits(ip("192.168.2.1")));
+        assert!(!filter.permits(ip("192.168.1.1")));
+        assert!(!filter.permits(ip("::ffff:192.168.1.1")));
+        assert!(!filter.permits(ip("10.0.0.1")));
+    }
+
+    #[test]
+    fn empty_allowlist_allows_everything_not_denied() {
+        let filter = filter(&[], &["203.0.113.0/24"]);
+        assert!(filter.permits(ip("8.8.8.8")));
+        assert!(filter.permits(ip("2001:db8::1")));
+        assert!(!filter.permits(ip("203.0.113.9")));
+        assert!(NetworkFilter::default().permits(ip("8.8.8.8")));
+    }
+
+    #[test]
+    fn default_allows_local_networks_only() {
+        let defaults = default_allowed_networks();
+        let defaults: Vec<&str> = defaults.iter().map(String::as_str).collect();
+        let filter = filter(&defaults, &[]);
+        for local in ["127.0.0.1", "10.0.0.5", "172.16.4.4", "192.168.1.20", "169.254.1.1", "::1", "fd00::1", "fe80::1"] {
+            assert!(filter.permits(ip(local)), "{} rejected", local);
+        }
+        for public in ["8.8.8.8", "172.32.0.1", "2001:db8::1"] {
+            assert!(!filter.permits(ip(public)), "{} allowed", public);
+        }
+    }
+}
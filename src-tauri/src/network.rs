@@ -0,0 +1,179 @@
+use serde::Serialize;
+use socket2::{Domain, Protocol, Socket, Type};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use tokio::net::TcpListener;
+
+use crate::config::Config;
+
+// Where the server listens, from `Config.bind_address`:
+// "all" (every interface), "loopback" (USB tethering / adb reverse), or a
+// specific interface address.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BindTarget {
+    All,
+    Loopback,
+    Address(IpAddr),
+}
+
+impl BindTarget {
+    pub fn from_config(config: &Config) -> Result<Self, String> {
+        match config.bind_address.trim().to_lowercase().as_str() {
+            "" | "all" => Ok(BindTarget::All),
+            "loopback" | "localhost" => Ok(BindTarget::Loopback),
+            other => other
+                .parse()
+                .map(BindTarget::Address)
+                .map_err(|_| format!("Invalid bind address: {}", config.bind_address)),
+        }
+    }
+
+    pub fn socket_addr(&self, port: u16, dual_stack: bool) -> SocketAddr {
+        let ip = match self {
+            BindTarget::All if dual_stack => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+            BindTarget::All => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            // adb reverse and most USB forwarders target 127.0.0.1
+            BindTarget::Loopback => IpAddr::V4(Ipv4Addr::LOCALHOST),
+            BindTarget::Address(ip) => *ip,
+        };
+        SocketAddr::new(ip, port)
+    }
+
+    // Addresses the phone should try, best first
+    pub fn pairing_hosts(&self, dual_stack: bool) -> Vec<IpAddr> {
+        match self {
+            BindTarget::Loopback => vec![IpAddr::V4(Ipv4Addr::LOCALHOST)],
+            BindTarget::Address(ip) if !ip.is_unspecified() => vec![*ip],
+            _ => {
+                let hosts: Vec<IpAddr> = list_interfaces()
+                    .into_iter()
+                    .filter(|i| i.kind != InterfaceKind::Loopback)
+                    .filter(|i| dual_stack || i.address.is_ipv4())
+                    .map(|i| i.address)
+                    .collect();
+                if hosts.is_empty() {
+                    vec![IpAddr::V4(Ipv4Addr::LOCALHOST)]
+                } else {
+                    hosts
+                }
+            }
+        }
+    }
+
+    pub fn pairing_ip(&self, dual_stack: bool) -> IpAddr {
+        self.pairing_hosts(dual_stack)[0]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InterfaceKind {
+    Wifi,
+    Ethernet,
+    Other,
+    Vpn,
+    Virtual,
+    Loopback,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NetworkInterface {
+    pub name: String,
+    pub address: IpAddr,
+    pub kind: InterfaceKind,
+    // Lower is more likely to be reachable from a phone on the same Wi-Fi
+    pub rank: u8,
+}
+
+// Every usable local address, most reachable first. IPv6 link-local
+// addresses are skipped since they need a scope id the phone can't know.
+pub fn list_interfaces() -> Vec<NetworkInterface> {
+    let netifs = match local_ip_address::list_afinet_netifas() {
+        Ok(list) => list,
+        Err(e) => {
+            eprintln!("Failed to list network interfaces: {}", e);
+            return Vec::new();
+        }
+    };
+
+    let mut interfaces: Vec<NetworkInterface> = netifs
+        .into_iter()
+        .filter(|(_, ip)| !ip.is_unspecified())
+        .filter(|(_, ip)| !matches!(ip, IpAddr::V6(v6) if v6.segments()[0] & 0xffc0 == 0xfe80))
+        .map(|(name, address)| {
+            let kind = classify(&name, address);
+            let rank = rank(kind, address);
+            NetworkInterface { name, address, kind, rank }
+        })
+        .collect();
+
+    interfaces.sort_by_key(|i| i.rank);
+    interfaces
+}
+
+// Guesses the adapter type from its name. Covers Windows friendly names
+// ("Wi-Fi", "vEthernet (WSL)") as well as Linux and macOS device names.
+fn classify(name: &str, address: IpAddr) -> InterfaceKind {
+    const VIRTUAL: &[&str] = &[
+        "docker", "veth", "br-", "virbr", "vmnet", "vboxnet", "virtualbox", "vethernet",
+        "hyper-v", "wsl", "vmware", "lxc", "lxd", "podman", "cni", "awdl", "llw", "bridge",
+    ];
+    const VPN: &[&str] = &[
+        "tun", "tap", "utun", "wg", "tailscale", "zerotier", "ppp", "ipsec", "vpn", "nordlynx",
+    ];
+
+    let name = name.to_lowercase();
+
+    if address.is_loopback() || name == "lo" || name.starts_with("loopback") {
+        InterfaceKind::Loopback
+    } else if VIRTUAL.iter().any(|v| name.contains(v)) {
+        InterfaceKind::Virtual
+    } else if VPN.iter().any(|v| name.starts_with(v) || name.contains(&format!(" {}", v))) {
+        InterfaceKind::Vpn
+    } else if name.contains("wi-fi") || name.contains("wifi") || name.contains("wireless") || name.starts_with("wl") {
+        InterfaceKind::Wifi
+    } else if name.contains("ethernet") || name.starts_with("eth") || name.starts_with("en") {
+        InterfaceKind::Ethernet
+    } else {
+        InterfaceKind::Other
+    }
+}
+
+fn rank(kind: InterfaceKind, address: IpAddr) -> u8 {
+    let base = match kind {
+        InterfaceKind::Wifi => 0,
+        InterfaceKind::Ethernet => 2,
+        InterfaceKind::Other => 4,
+        InterfaceKind::Vpn => 6,
+        InterfaceKind::Virtual => 8,
+        InterfaceKind::Loopback => 10,
+    };
+    // APIPA addresses mean DHCP failed; IPv6 is tried after IPv4
+    let demoted = match address {
+        IpAddr::V4(v4) => v4.is_link_local(),
+        IpAddr::V6(_) => true,
+    };
+    if demoted && kind != InterfaceKind::Loopback {
+        base + 1
+    } else {
+        base
+    }
+}
+
+// Binds the server socket. With `dual_stack`, an IPv6 wildcard bind also
+// accepts IPv4 clients (as v4-mapped addresses).
+pub fn bind_listener(addr: SocketAddr, dual_stack: bool) -> std::io::Result<TcpListener> {
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+
+    if addr.is_ipv6() {
+        socket.set_only_v6(!dual_stack)?;
+    }
+    // Matches tokio's TcpListener::bind: lets a restarted server reuse the port
+    #[cfg(unix)]
+    socket.set_reuse_address(true)?;
+
+    socket.set_nonblocking(true)?;
+    socket.bind(&addr.into())?;
+    socket.listen(1024)?;
+
+    TcpListener::from_std(socket.into())
+}
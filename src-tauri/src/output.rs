@@ -0,0 +1,134 @@
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+
+use crate::bindings::Action;
+use crate::config::Config;
+
+// Which backend turns controller input into OS input, from
+// `Config.output_backend`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputBackendKind {
+    // Keystrokes through enigo, using the configured key bindings
+    #[default]
+    Keyboard,
+    // Remembers everything and sends nothing (dry run)
+    Recording,
+    // One virtual gamepad per player (Linux uinput)
+    Gamepad,
+}
+
+// Receives controller input by player slot and action. Each backend decides
+// what an action maps to; the keyboard has a single set of keys for everyone.
+pub trait OutputBackend: Send {
+    // A client took this player slot
+    fn connect(&mut self, _player: u8) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn press(&mut self, player: u8, action: Action) -> Result<(), String>;
+
+    fn release(&mut self, player: u8, action: Action) -> Result<(), String>;
+
+    // Analog controls, `value` in -1.0..=1.0
+    fn axis(&mut self, player: u8, action: Action, value: f32) -> Result<(), String>;
+
+    // Lets everything go for a player, e.g. when its client disconnects
+    fn release_all(&mut self, _player: u8) {}
+
+    // Picks up saved settings (key bindings) while the server runs
+    fn configure(&mut self, _config: &Config) {}
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum OutputEvent {
+    Connect(u8),
+    Press(u8, Action),
+    Release(u8, Action),
+    Axis(u8, Action, f32),
+    ReleaseAll(u8),
+}
+
+// Keeps every output event in memory. Clones share the same log, so a test
+// can hand one to the server and inspect another.
+#[derive(Debug, Clone, Default)]
+pub struct RecordingOutput {
+    events: Arc<Mutex<Vec<OutputEvent>>>,
+}
+
+impl RecordingOutput {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn events(&self) -> Vec<OutputEvent> {
+        self.events.lock().unwrap().clone()
+    }
+
+    fn record(&self, event: OutputEvent) {
+        self.events.lock().unwrap().push(event);
+    }
+}
+
+impl OutputBackend for RecordingOutput {
+    fn connect(&mut self, player: u8) -> Result<(), String> {
+        self.record(OutputEvent::Connect(player));
+        Ok(())
+    }
+
+    fn press(&mut self, player: u8, action: Action) -> Result<(), String> {
+        self.record(OutputEvent::Press(player, action));
+        Ok(())
+    }
+
+    fn release(&mut self, player: u8, action: Action) -> Result<(), String> {
+        self.record(OutputEvent::Release(player, action));
+        Ok(())
+    }
+
+    fn axis(&mut self, player: u8, action: Action, value: f32) -> Result<(), String> {
+        self.record(OutputEvent::Axis(player, action, value));
+        Ok(())
+    }
+
+    fn release_all(&mut self, player: u8) {
+        self.record(OutputEvent::ReleaseAll(player));
+    }
+}
+
+// Builds the backend selected in config
+pub fn create(config: &Config) -> Result<Box<dyn OutputBackend>, String> {
+    match config.output_backend {
+        #[cfg(feature = "enigo-output")]
+        OutputBackendKind::Keyboard => Ok(Box::new(crate::keyboard::EnigoKeyboard::new(config)?)),
+        #[cfg(not(feature = "enigo-output"))]
+        OutputBackendKind::Keyboard => Err("built without keyboard output (enigo-output feature)".to_string()),
+        OutputBackendKind::Recording => Ok(Box::new(RecordingOutput::new())),
+        #[cfg(target_os = "linux")]
+        OutputBackendKind::Gamepad => Ok(Box::new(crate::gamepad::VirtualGamepads::uinput()?)),
+        #[cfg(not(target_os = "linux"))]
+        OutputBackendKind::Gamepad => Err("virtual gamepads need Linux (uinput)".to_string()),
+    }
+}
+
+pub const MAX_PLAYERS: u8 = 8;
+
+// Hands each connected client its own player number, lowest free first
+#[derive(Debug, Default)]
+pub struct PlayerSlots {
+    taken: [bool; MAX_PLAYERS as usize],
+}
+
+impl PlayerSlots {
+    pub fn claim(&mut self) -> Option<u8> {
+        let slot = self.taken.iter().position(|taken| !taken)?;
+        self.taken[slot] = true;
+        Some(slot as u8)
+    }
+
+    pub fn free(&mut self, slot: u8) {
+        if let Some(taken) = self.taken.get_mut(slot as usize) {
+            *taken = false;
+        }
+    }
+}
@@ -0,0 +1,446 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::net::Ipv4Addr;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use crate::config::Config;
+use crate::network::BindTarget;
+
+// Pairing payload carried by the QR code:
+//
+//   mobilehero://pair?v=1&hosts=192.168.1.20,10.0.0.5&port=8080&pin=482913&name=Living%20Room
+//
+// v      format version, bumped on incompatible changes (required)
+// hosts  comma separated candidate addresses, best first (required)
+// port   WebSocket port (required)
+// pin    session PIN, omitted when the user has to type it
+// fp     TLS certificate fingerprint, once the server speaks TLS
+// name   friendly PC name
+//
+// Values are percent-encoded. Parsers ignore parameters they don't know.
+pub const PAIRING_URI_VERSION: u32 = 1;
+const PAIRING_URI_PREFIX: &str = "mobilehero://pair?";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PairingInfo {
+    pub version: u32,
+    pub hosts: Vec<String>,
+    pub port: u16,
+    pub pin: Option<String>,
+    pub fingerprint: Option<String>,
+    pub name: Option<String>,
+}
+
+impl PairingInfo {
+    // Pairing details for a server running with `config`. `pin` is the
+    // session PIN, if any; the configured PIN is only stored hashed, so the
+    // phone prompts for it instead.
+    pub fn for_server(config: &Config, pin: Option<String>) -> Result<Self, String> {
+        let bind = BindTarget::from_config(config)?;
+        Ok(Self {
+            version: PAIRING_URI_VERSION,
+            hosts: bind
+                .pairing_hosts(config.dual_stack)
+                .iter()
+                .take(4)
+                .map(|ip| ip.to_string())
+                .collect(),
+            port: config.port,
+            pin,
+            fingerprint: None,
+            name: Some(config.display_name()),
+        })
+    }
+
+    pub fn to_uri(&self) -> String {
+        let hosts: Vec<String> = self.hosts.iter().map(|h| encode(h)).collect();
+        let mut uri = format!(
+            "{}v={}&hosts={}&port={}",
+            PAIRING_URI_PREFIX,
+            self.version,
+            hosts.join(","),
+            self.port
+        );
+
+        for (key, value) in [("pin", &self.pin), ("fp", &self.fingerprint), ("name", &self.name)] {
+            if let Some(value) = value {
+                uri.push_str(&format!("&{}={}", key, encode(value)));
+            }
+        }
+        uri
+    }
+
+    pub fn parse(uri: &str) -> Result<Self, String> {
+        let uri = uri.trim();
+        let query = uri
+            .get(..PAIRING_URI_PREFIX.len())
+            .filter(|prefix| prefix.eq_ignore_ascii_case(PAIRING_URI_PREFIX))
+            .map(|_| &uri[PAIRING_URI_PREFIX.len()..])
+            .ok_or_else(|| "Not a MobileHero pairing URI".to_string())?;
+
+        let mut version = None;
+        let mut hosts = Vec::new();
+        let mut port = None;
+        let mut pin = None;
+        let mut fingerprint = None;
+        let mut name = None;
+
+        for pair in query.split('&').filter(|p| !p.is_empty()) {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            match key {
+                "v" => {
+                    version = Some(
+                        value
+                            .parse::<u32>()
+                            .map_err(|_| format!("Invalid version: {}", value))?,
+                    )
+                }
+                "hosts" => {
+                    hosts = value
+                        .split(',')
+                        .filter(|h| !h.is_empty())
+                        .map(decode)
+                        .collect::<Result<_, _>>()?
+                }
+                "port" => {
+                    port = Some(
+                        value
+                            .parse::<u16>()
+                            .map_err(|_| format!("Invalid port: {}", value))?,
+                    )
+                }
+                "pin" => pin = Some(decode(value)?),
+                "fp" => fingerprint = Some(decode(value)?),
+                "name" => name = Some(decode(value)?),
+                _ => {}
+            }
+        }
+
+        let version = version.ok_or_else(|| "Missing version".to_string())?;
+        if version > PAIRING_URI_VERSION {
+            return Err(format!("Unsupported pairing format version {}", version));
+        }
+        if hosts.is_empty() {
+            return Err("Missing hosts".to_string());
+        }
+        let port = port.ok_or_else(|| "Missing port".to_string())?;
+
+        Ok(Self {
+            version,
+            hosts,
+            port,
+            pin,
+            fingerprint,
+            name,
+        })
+    }
+}
+
+impl fmt::Display for PairingInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_uri())
+    }
+}
+
+impl FromStr for PairingInfo {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+// Percent-encodes everything except RFC 3986 unreserved characters (and ':'
+// so IPv6 hosts stay readable)
+fn encode(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~:".contains(&byte) {
+            out.push(byte as char);
+        } else {
+            out.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    out
+}
+
+fn decode(value: &str) -> Result<String, String> {
+    let bytes = value.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => {
+                // from_str_radix alone would also take a sign, e.g. "%+1"
+                let hex = value
+                    .get(i + 1..i + 3)
+                    .filter(|h| h.bytes().all(|b| b.is_ascii_hexdigit()))
+                    .and_then(|h| u8::from_str_radix(h, 16).ok())
+                    .ok_or_else(|| format!("Invalid percent-encoding in: {}", value))?;
+                out.push(hex);
+                i += 3;
+            }
+            b'+' => {
+                out.push(b' ');
+                i += 1;
+            }
+            b => {
+                out.push(b);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(out).map_err(|_| format!("Invalid UTF-8 in: {}", value))
+}
+
+// Short code for typing in by hand when the QR can't be scanned, e.g.
+// "7K2M-Q9XF". Eight Crockford base32 characters carry 40 bits:
+//
+//   last octet of the PC's IPv4 address (the phone fills in the rest from
+//   its own /24) | port (16 bits) | one-time secret (16 bits)
+//
+// The secret is only useful once and for PAIRING_CODE_TTL; together with the
+// auth lockout that leaves no room for guessing it.
+pub const PAIRING_CODE_TTL: Duration = Duration::from_secs(5 * 60);
+// A code is burned after this many wrong guesses from anyone
+const PAIRING_CODE_MAX_FAILURES: u32 = 10;
+const CROCKFORD: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+const CODE_LEN: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PairingCode {
+    pub host_octet: u8,
+    pub port: u16,
+    pub secret: u16,
+}
+
+impl PairingCode {
+    pub fn encode(&self) -> String {
+        let bits = (self.host_octet as u64) << 32 | (self.port as u64) << 16 | self.secret as u64;
+        let chars: String = (0..CODE_LEN)
+            .rev()
+            .map(|i| CROCKFORD[((bits >> (i * 5)) & 0x1f) as usize] as char)
+            .collect();
+        format!("{}-{}", &chars[..4], &chars[4..])
+    }
+
+    // Accepts any case, with or without separators, and the usual Crockford
+    // look-alikes (O for 0, I and L for 1)
+    pub fn decode(code: &str) -> Result<Self, String> {
+        let mut bits: u64 = 0;
+        let mut len = 0;
+        for c in code.chars().filter(|c| !matches!(c, '-' | ' ')) {
+            let c = match c.to_ascii_uppercase() {
+                'O' => '0',
+                'I' | 'L' => '1',
+                c => c,
+            };
+            let value = CROCKFORD
+                .iter()
+                .position(|&b| b as char == c)
+                .ok_or_else(|| format!("Invalid character in pairing code: {}", c))?;
+            bits = bits << 5 | value as u64;
+            len += 1;
+        }
+        if len != CODE_LEN {
+            return Err("Pairing codes are 8 characters long".to_string());
+        }
+
+        Ok(Self {
+            host_octet: (bits >> 32) as u8,
+            port: (bits >> 16) as u16,
+            secret: bits as u16,
+        })
+    }
+}
+
+// The code currently on screen, held by the server until redeemed or expired
+pub struct IssuedPairingCode {
+    code: PairingCode,
+    expires_at: Instant,
+    failures: u32,
+}
+
+impl IssuedPairingCode {
+    pub fn new(host: Ipv4Addr, port: u16) -> Self {
+        let code = PairingCode {
+            host_octet: host.octets()[3],
+            port,
+            secret: rand::thread_rng().gen(),
+        };
+        Self {
+            code,
+            expires_at: Instant::now() + PAIRING_CODE_TTL,
+            failures: 0,
+        }
+    }
+
+    pub fn code(&self) -> PairingCode {
+        self.code
+    }
+
+    pub fn is_expired(&self) -> bool {
+        Instant::now() >= self.expires_at
+    }
+
+    pub fn can_retry(&self) -> bool {
+        !self.is_expired() && self.failures < PAIRING_CODE_MAX_FAILURES
+    }
+
+    // Checks a code typed on the phone. Callers drop the issued code once it
+    // has been redeemed or can no longer be retried.
+    pub fn verify(&mut self, given: &str) -> bool {
+        if self.is_expired() {
+            return false;
+        }
+        let matches = PairingCode::decode(given)
+            .map(|given| {
+                // Same-length integer compare; nothing secret-dependent branches
+                (given.secret ^ self.code.secret) | (given.port ^ self.code.port) == 0
+            })
+            .unwrap_or(false);
+        if !matches {
+            self.failures += 1;
+        }
+        matches
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info() -> PairingInfo {
+        PairingInfo {
+            version: PAIRING_URI_VERSION,
+            hosts: vec!["192.168.1.20".to_string(), "10.0.0.5".to_string()],
+            port: 8080,
+            pin: Some("482913".to_string()),
+            fingerprint: None,
+            name: Some("Living Room".to_string()),
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let info = info();
+        let uri = info.to_uri();
+        assert_eq!(
+            uri,
+            "mobilehero://pair?v=1&hosts=192.168.1.20,10.0.0.5&port=8080&pin=482913&name=Living%20Room"
+        );
+        assert_eq!(PairingInfo::parse(&uri), Ok(info));
+    }
+
+    #[test]
+    fn round_trip_without_optional_fields() {
+        let info = PairingInfo {
+            pin: None,
+            name: None,
+            ..info()
+        };
+        assert_eq!(PairingInfo::parse(&info.to_uri()), Ok(info));
+    }
+
+    #[test]
+    fn round_trip_ipv6_hosts() {
+        let info = PairingInfo {
+            hosts: vec![
+                "fe80::1%eth0".to_string(),
+                "2001:db8::20".to_string(),
+                "192.168.1.20".to_string(),
+            ],
+            ..info()
+        };
+        let uri = info.to_uri();
+        // The colons stay readable, the zone separator is escaped
+        assert!(uri.contains("hosts=fe80::1%25eth0,2001:db8::20,192.168.1.20&"));
+        assert_eq!(PairingInfo::parse(&uri), Ok(info));
+    }
+
+    #[test]
+    fn round_trip_encoded_name() {
+        let info = PairingInfo {
+            name: Some("Küche & Wohnzimmer = 100%, ok?".to_string()),
+            fingerprint: Some("AB:CD:EF".to_string()),
+            ..info()
+        };
+        let uri = info.to_uri();
+        assert!(uri.contains("&name=K%C3%BCche%20%26%20Wohnzimmer%20%3D%20100%25%2C%20ok%3F"));
+        assert_eq!(PairingInfo::parse(&uri), Ok(info));
+    }
+
+    #[test]
+    fn decodes_plus_as_space() {
+        let parsed = PairingInfo::parse("mobilehero://pair?v=1&hosts=10.0.0.5&port=8080&name=Living+Room").unwrap();
+        assert_eq!(parsed.name.as_deref(), Some("Living Room"));
+    }
+
+    #[test]
+    fn rejects_missing_required_fields() {
+        assert_eq!(
+            PairingInfo::parse("mobilehero://pair?hosts=10.0.0.5&port=8080"),
+            Err("Missing version".to_string())
+        );
+        assert_eq!(
+            PairingInfo::parse("mobilehero://pair?v=1&port=8080"),
+            Err("Missing hosts".to_string())
+        );
+        assert_eq!(
+            PairingInfo::parse("mobilehero://pair?v=1&hosts=&port=8080"),
+            Err("Missing hosts".to_string())
+        );
+        assert_eq!(
+            PairingInfo::parse("mobilehero://pair?v=1&hosts=10.0.0.5"),
+            Err("Missing port".to_string())
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_values() {
+        assert!(PairingInfo::parse("mobilehero://pair?v=one&hosts=10.0.0.5&port=8080").is_err());
+        assert!(PairingInfo::parse("mobilehero://pair?v=1&hosts=10.0.0.5&port=70000").is_err());
+        assert!(PairingInfo::parse("mobilehero://pair?v=1&hosts=10.0.0.5&port=8080&name=%G1").is_err());
+        assert!(PairingInfo::parse("mobilehero://pair?v=1&hosts=10.0.0.5&port=8080&name=%FF").is_err());
+        assert!(PairingInfo::parse("mobilehero://pair?v=1&hosts=10.0.0.5&port=8080&name=%+1").is_err());
+        assert!(PairingInfo::parse("mobilehero://pair?v=1&hosts=10.0.0.5&port=8080&name=%-1").is_err());
+        assert!(PairingInfo::parse("mobilehero://pair?v=1&hosts=10.0.0.5&port=8080&name=%4").is_err());
+        assert!(PairingInfo::parse("https://example.com/?v=1&hosts=10.0.0.5&port=8080").is_err());
+    }
+
+    #[test]
+    fn rejects_future_version() {
+        let uri = format!("mobilehero://pair?v={}&hosts=10.0.0.5&port=8080", PAIRING_URI_VERSION + 1);
+        assert_eq!(
+            PairingInfo::parse(&uri),
+            Err(format!("Unsupported pairing format version {}", PAIRING_URI_VERSION + 1))
+        );
+    }
+
+    #[test]
+    fn ignores_unknown_parameters() {
+        let uri = "MobileHero://Pair?v=1&tls=1&hosts=10.0.0.5&port=8080&&flag&future=x%20y";
+        let parsed = PairingInfo::parse(uri).unwrap();
+        assert_eq!(parsed.hosts, ["10.0.0.5"]);
+        assert_eq!(parsed.port, 8080);
+        assert_eq!(parsed.pin, None);
+    }
+
+    #[test]
+    fn pairing_code_round_trip() {
+        let code = PairingCode {
+            host_octet: 20,
+            port: 8080,
+            secret: 0xBEEF,
+        };
+        let encoded = code.encode();
+        assert_eq!(encoded.len(), 9);
+        assert_eq!(PairingCode::decode(&encoded), Ok(code));
+        let sloppy = encoded.to_lowercase().replace('-', " ").replace('0', "o").replace('1', "l");
+        assert_eq!(PairingCode::decode(&sloppy), Ok(code));
+        assert!(PairingCode::decode("7K2M-Q9X").is_err());
+        assert!(PairingCode::decode("7K2M-Q9XU").is_err());
+    }
+}
@@ -0,0 +1,151 @@
+use base64::Engine;
+use image::{ImageEncoder, Rgba};
+use qrcode::render::{svg, unicode};
+use qrcode::{EcLevel, QrCode};
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QrFormat {
+    // `data:image/png;base64,...` URL
+    #[default]
+    Png,
+    // Raw SVG markup, crisp at any size
+    Svg,
+    // UTF-8 half-block art for terminals and consoles
+    Text,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+pub enum ErrorCorrection {
+    #[serde(rename = "L")]
+    Low,
+    #[default]
+    #[serde(rename = "M")]
+    Medium,
+    #[serde(rename = "Q")]
+    Quartile,
+    #[serde(rename = "H")]
+    High,
+}
+
+impl From<ErrorCorrection> for EcLevel {
+    fn from(level: ErrorCorrection) -> Self {
+        match level {
+            ErrorCorrection::Low => EcLevel::L,
+            ErrorCorrection::Medium => EcLevel::M,
+            ErrorCorrection::Quartile => EcLevel::Q,
+            ErrorCorrection::High => EcLevel::H,
+        }
+    }
+}
+
+// Everything `render` can be asked for. Missing fields in JSON fall back to
+// the defaults, which match the launcher's original PNG output.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct QrOptions {
+    pub format: QrFormat,
+    pub error_correction: ErrorCorrection,
+    pub quiet_zone: bool,
+    // Pixels per module (PNG and SVG only)
+    pub module_size: u32,
+    // "#rrggbb" or "#rgb". For text output only their relative brightness
+    // matters: a dark color brighter than the light one inverts the art for
+    // dark terminals.
+    pub dark_color: Option<String>,
+    pub light_color: Option<String>,
+}
+
+impl Default for QrOptions {
+    fn default() -> Self {
+        Self {
+            format: QrFormat::Png,
+            error_correction: ErrorCorrection::Medium,
+            quiet_zone: true,
+            module_size: 8,
+            dark_color: None,
+            light_color: None,
+        }
+    }
+}
+
+pub fn render(data: &str, options: &QrOptions) -> Result<String, String> {
+    let code = QrCode::with_error_correction_level(data.as_bytes(), options.error_correction.into())
+        .map_err(|e| e.to_string())?;
+    let dark = parse_color(options.dark_color.as_deref().unwrap_or("#000000"))?;
+    let light = parse_color(options.light_color.as_deref().unwrap_or("#ffffff"))?;
+    let module_size = options.module_size.max(1);
+
+    match options.format {
+        QrFormat::Png => {
+            let image = code
+                .render::<Rgba<u8>>()
+                .quiet_zone(options.quiet_zone)
+                .module_dimensions(module_size, module_size)
+                .dark_color(Rgba([dark[0], dark[1], dark[2], 255]))
+                .light_color(Rgba([light[0], light[1], light[2], 255]))
+                .build();
+
+            let mut png_data: Vec<u8> = Vec::new();
+            let encoder = image::codecs::png::PngEncoder::new(&mut png_data);
+            encoder.write_image(
+                image.as_raw(),
+                image.width(),
+                image.height(),
+                image::ExtendedColorType::Rgba8,
+            ).map_err(|e: image::ImageError| e.to_string())?;
+
+            let base64 = base64::engine::general_purpose::STANDARD.encode(&png_data);
+            Ok(format!("data:image/png;base64,{}", base64))
+        }
+        QrFormat::Svg => {
+            let (dark, light) = (to_hex(dark), to_hex(light));
+            Ok(code
+                .render::<svg::Color>()
+                .quiet_zone(options.quiet_zone)
+                .module_dimensions(module_size, module_size)
+                .dark_color(svg::Color(&dark))
+                .light_color(svg::Color(&light))
+                .build())
+        }
+        QrFormat::Text => {
+            let inverted = luminance(dark) > luminance(light);
+            let (dark, light) = if inverted {
+                (unicode::Dense1x2::Light, unicode::Dense1x2::Dark)
+            } else {
+                (unicode::Dense1x2::Dark, unicode::Dense1x2::Light)
+            };
+            Ok(code
+                .render::<unicode::Dense1x2>()
+                .quiet_zone(options.quiet_zone)
+                .dark_color(dark)
+                .light_color(light)
+                .build())
+        }
+    }
+}
+
+fn parse_color(color: &str) -> Result<[u8; 3], String> {
+    let hex = color.trim().trim_start_matches('#');
+    if !hex.is_ascii() {
+        return Err(format!("Invalid color: {}", color));
+    }
+    let expanded: String = match hex.len() {
+        3 => hex.chars().flat_map(|c| [c, c]).collect(),
+        6 => hex.to_string(),
+        _ => return Err(format!("Invalid color: {}", color)),
+    };
+    let channel = |i: usize| {
+        u8::from_str_radix(&expanded[i..i + 2], 16).map_err(|_| format!("Invalid color: {}", color))
+    };
+    Ok([channel(0)?, channel(2)?, channel(4)?])
+}
+
+fn to_hex(rgb: [u8; 3]) -> String {
+    format!("#{:02x}{:02x}{:02x}", rgb[0], rgb[1], rgb[2])
+}
+
+fn luminance(rgb: [u8; 3]) -> u32 {
+    299 * rgb[0] as u32 + 587 * rgb[1] as u32 + 114 * rgb[2] as u32
+}
@@ -0,0 +1,174 @@
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+use crate::bindings::{Action, ActionGroup};
+
+// Server-side key repeat for held navigation buttons (`Config.auto_repeat`).
+// Synthetic presses rarely trigger the OS's own autorepeat.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AutoRepeatConfig {
+    pub enabled: bool,
+    // Hold time before the first repeat
+    pub delay_ms: u64,
+    // Time between the first repeats
+    pub interval_ms: u64,
+    // Each repeat multiplies the interval by this (1.0 keeps a steady
+    // rate), down to `min_interval_ms`
+    pub acceleration: f32,
+    pub min_interval_ms: u64,
+}
+
+impl Default for AutoRepeatConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            delay_ms: 400,
+            interval_ms: 120,
+            acceleration: 0.9,
+            min_interval_ms: 40,
+        }
+    }
+}
+
+// Floor for both intervals; 0 would repeat on every pass of the
+// connection's event loop
+const MIN_INTERVAL_MS: u64 = 10;
+
+#[derive(Debug, Clone, Copy)]
+struct Repeat {
+    due: Instant,
+    interval: Duration,
+}
+
+// Repeat timers for one connection's held nav buttons
+pub struct AutoRepeat {
+    config: AutoRepeatConfig,
+    held: [Option<Repeat>; Action::COUNT],
+}
+
+impl AutoRepeat {
+    pub fn new(config: &AutoRepeatConfig) -> Self {
+        let config = AutoRepeatConfig {
+            interval_ms: config.interval_ms.max(MIN_INTERVAL_MS),
+            min_interval_ms: config.min_interval_ms.max(MIN_INTERVAL_MS),
+            ..config.clone()
+        };
+        Self {
+            config,
+            held: [None; Action::COUNT],
+        }
+    }
+
+    pub fn pressed(&mut self, action: Action, now: Instant) {
+        if !self.config.enabled || action.group() != ActionGroup::Nav {
+            return;
+        }
+        self.held[action.index()].get_or_insert(Repeat {
+            due: now + Duration::from_millis(self.config.delay_ms),
+            interval: Duration::from_millis(self.config.interval_ms),
+        });
+    }
+
+    pub fn released(&mut self, action: Action) {
+        self.held[action.index()] = None;
+    }
+
+    // When the next repeat is due, if any button is held
+    pub fn next_due(&self) -> Option<Instant> {
+        self.held.iter().flatten().map(|repeat| repeat.due).min()
+    }
+
+    // Actions to press again now, rescheduling each at its faster interval
+    pub fn due(&mut self, now: Instant) -> Vec<Action> {
+        let min_interval = Duration::from_millis(self.config.min_interval_ms);
+        let acceleration = self.config.acceleration.clamp(0.0, 1.0);
+        let mut due = Vec::new();
+        for (action, slot) in Action::ALL.iter().zip(self.held.iter_mut()) {
+            let Some(repeat) = slot else {
+                continue;
+            };
+            if repeat.due > now {
+                continue;
+            }
+            due.push(*action);
+            repeat.due = now + repeat.interval;
+            repeat.interval = repeat.interval.mul_f32(acceleration).max(min_interval);
+        }
+        due
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    // Intervals are scaled in f32, so allow for rounding
+    fn assert_due(repeat: &AutoRepeat, expected: Instant) {
+        let due = repeat.next_due().expect("nothing due");
+        let off = due.max(expected) - due.min(expected);
+        assert!(off < ms(1), "due {:?} off by {:?}", due, off);
+    }
+
+    #[test]
+    fn repeats_nav_after_delay_and_accelerates() {
+        let config = AutoRepeatConfig {
+            delay_ms: 400,
+            interval_ms: 100,
+            acceleration: 0.5,
+            min_interval_ms: 30,
+            ..Default::default()
+        };
+        let mut repeat = AutoRepeat::new(&config);
+        let start = Instant::now();
+        repeat.pressed(Action::Down, start);
+        repeat.pressed(Action::Green, start);
+
+        assert_due(&repeat, start + ms(400));
+        assert!(repeat.due(start + ms(399)).is_empty());
+        // Each gap shrinks by the acceleration, down to min_interval_ms
+        let mut now = start + ms(400);
+        for gap in [100, 50, 30, 30] {
+            assert_eq!(repeat.due(now), [Action::Down]);
+            assert_due(&repeat, now + ms(gap));
+            now = repeat.next_due().unwrap();
+        }
+
+        repeat.released(Action::Down);
+        assert_eq!(repeat.next_due(), None);
+    }
+
+    #[test]
+    fn zero_intervals_are_clamped() {
+        let config = AutoRepeatConfig {
+            delay_ms: 0,
+            interval_ms: 0,
+            min_interval_ms: 0,
+            ..Default::default()
+        };
+        let mut repeat = AutoRepeat::new(&config);
+        let start = Instant::now();
+        repeat.pressed(Action::Up, start);
+
+        assert_eq!(repeat.due(start), [Action::Up]);
+        assert_due(&repeat, start + ms(MIN_INTERVAL_MS));
+        let now = repeat.next_due().unwrap();
+        assert_eq!(repeat.due(now), [Action::Up]);
+        assert_due(&repeat, now + ms(MIN_INTERVAL_MS));
+    }
+
+    #[test]
+    fn disabled_never_repeats() {
+        let config = AutoRepeatConfig {
+            enabled: false,
+            ..Default::default()
+        };
+        let mut repeat = AutoRepeat::new(&config);
+        repeat.pressed(Action::Left, Instant::now());
+        assert_eq!(repeat.next_due(), None);
+    }
+}
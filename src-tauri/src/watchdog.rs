@@ -0,0 +1,155 @@
+use std::time::{Duration, Instant};
+
+use crate::bindings::{Action, ActionGroup};
+use crate::config::Config;
+
+// How often connections look for stuck keys
+pub const CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+// Strums and drum hits are taps, so anything longer is a lost release.
+// Frets and star power may be sustained through a long note. Nav buttons
+// auto-repeat while held, so a lost release keeps scrolling until this
+// limit; it is enough to reach the end of a long song list.
+fn default_max_hold(action: Action) -> Duration {
+    let ms = match action.group() {
+        ActionGroup::Strum | ActionGroup::Drum => 1_000,
+        ActionGroup::Fret | ActionGroup::Action => 30_000,
+        ActionGroup::Nav => 10_000,
+    };
+    Duration::from_millis(ms)
+}
+
+// Tracks which actions a client holds and since when, so a lost release
+// message can't leave a key down forever
+pub struct HoldWatchdog {
+    limits: [Option<Duration>; Action::COUNT],
+    held: [Option<Instant>; Action::COUNT],
+}
+
+impl HoldWatchdog {
+    // Limits come from `Config.max_hold_ms` where set (0 never releases),
+    // else from the action's group
+    pub fn new(config: &Config) -> Self {
+        let limits = Action::ALL.map(|action| match config.max_hold_ms.get(action.name()) {
+            Some(0) => None,
+            Some(&ms) => Some(Duration::from_millis(ms)),
+            None => Some(default_max_hold(action)),
+        });
+        Self {
+            limits,
+            held: [None; Action::COUNT],
+        }
+    }
+
+    pub fn pressed(&mut self, action: Action, now: Instant) {
+        // A repeated press doesn't restart the clock
+        self.held[action.index()].get_or_insert(now);
+    }
+
+    pub fn released(&mut self, action: Action) {
+        self.held[action.index()] = None;
+    }
+
+    pub fn holds(&self, action: Action) -> bool {
+        self.held[action.index()].is_some()
+    }
+
+    // Actions held down right now, with how long they have been
+    pub fn held(&self, now: Instant) -> impl Iterator<Item = (Action, Duration)> + '_ {
+        Action::ALL
+            .iter()
+            .filter_map(move |&action| Some((action, now.duration_since(self.held[action.index()]?))))
+    }
+
+    // Forgets and returns the actions held past their limit, with how long
+    // they were held. The caller releases them.
+    pub fn expired(&mut self, now: Instant) -> Vec<(Action, Duration)> {
+        let expired: Vec<_> = self
+            .held(now)
+            .filter(|&(action, held)| self.limits[action.index()].is_some_and(|limit| held > limit))
+            .collect();
+        for &(action, _) in &expired {
+            self.released(action);
+        }
+        expired
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    fn expired(watchdog: &mut HoldWatchdog, now: Instant) -> Vec<Action> {
+        watchdog.expired(now).into_iter().map(|(action, _)| action).collect()
+    }
+
+    // One action from each group, past the limit documented in the README
+    #[test]
+    fn default_limits() {
+        let cases = [
+            (Action::StrumUp, 1_000),
+            (Action::DrumRed, 1_000),
+            (Action::Green, 30_000),
+            (Action::Starpower, 30_000),
+            (Action::Down, 10_000),
+        ];
+        for (action, limit) in cases {
+            let mut watchdog = HoldWatchdog::new(&Config::default());
+            let start = Instant::now();
+            watchdog.pressed(action, start);
+            assert!(expired(&mut watchdog, start + ms(limit)).is_empty(), "{}", action.name());
+            assert_eq!(expired(&mut watchdog, start + ms(limit + 1)), [action], "{}", action.name());
+        }
+    }
+
+    #[test]
+    fn config_overrides_and_disables() {
+        let mut config = Config::default();
+        config.max_hold_ms.insert("strum_up".to_string(), 5_000);
+        config.max_hold_ms.insert("green".to_string(), 0);
+        let mut watchdog = HoldWatchdog::new(&config);
+        let start = Instant::now();
+        for action in [Action::StrumUp, Action::StrumDown, Action::Green] {
+            watchdog.pressed(action, start);
+        }
+
+        assert_eq!(expired(&mut watchdog, start + ms(1_001)), [Action::StrumDown]);
+        assert!(expired(&mut watchdog, start + ms(5_000)).is_empty());
+        assert_eq!(expired(&mut watchdog, start + ms(5_001)), [Action::StrumUp]);
+        // 0 never releases
+        assert!(expired(&mut watchdog, start + ms(3_600_000)).is_empty());
+        assert!(watchdog.holds(Action::Green));
+    }
+
+    #[test]
+    fn repeated_press_keeps_the_first_time() {
+        let mut watchdog = HoldWatchdog::new(&Config::default());
+        let start = Instant::now();
+        watchdog.pressed(Action::StrumUp, start);
+        watchdog.pressed(Action::StrumUp, start + ms(900));
+        assert_eq!(expired(&mut watchdog, start + ms(1_001)), [Action::StrumUp]);
+    }
+
+    #[test]
+    fn release_and_expiry_forget_the_action() {
+        let mut watchdog = HoldWatchdog::new(&Config::default());
+        let start = Instant::now();
+        watchdog.pressed(Action::StrumUp, start);
+        watchdog.pressed(Action::StrumDown, start);
+        watchdog.released(Action::StrumUp);
+
+        let late = start + ms(2_000);
+        assert_eq!(watchdog.held(late).collect::<Vec<_>>(), [(Action::StrumDown, ms(2_000))]);
+        assert_eq!(watchdog.expired(late), [(Action::StrumDown, ms(2_000))]);
+        assert!(watchdog.expired(late).is_empty());
+        assert_eq!(watchdog.held(late).count(), 0);
+
+        // A new press starts a new clock
+        watchdog.pressed(Action::StrumDown, late);
+        assert!(expired(&mut watchdog, late + ms(1_000)).is_empty());
+    }
+}
@@ -2,48 +2,152 @@ use futures_util::{SinkExt, StreamExt};
 use serde::{Deserialize, Serialize};
 use std::net::SocketAddr;
 use std::sync::Arc;
-use tokio::net::{TcpListener, TcpStream};
+use tokio::net::TcpStream;
 use tokio::sync::Mutex;
 use tokio_tungstenite::{accept_async, tungstenite::Message};
-use tauri::{AppHandle, Emitter, Manager};
 
-use crate::config::{self, Config};
-use crate::keyboard;
+use crate::auth::{self, AuthLimiter, PinHash};
+use crate::bindings::{Action, ActionGroup, BindingTable};
+use crate::config::Config;
+use crate::discovery::{self, DiscoveryReply, MdnsAdvertiser};
+use crate::events::{EventSink, ServerEvent, ServerStatus};
+use crate::netfilter::NetworkFilter;
+use crate::injector::Injector;
+use crate::keys;
+use crate::macros::MacroRunner;
+use crate::repeat::AutoRepeat;
+use crate::watchdog::{self, HoldWatchdog};
+use crate::output::{self, OutputBackendKind, PlayerSlots, RecordingOutput, MAX_PLAYERS};
+use crate::pairing::IssuedPairingCode;
+use crate::network::{self, BindTarget};
 
 // ... imports ...
 
+// Bumped whenever the wire protocol changes incompatibly.
+// 1: plain PIN auth, 2: challenge-response auth
+pub const PROTOCOL_VERSION: u32 = 2;
+
 pub struct ServerState {
     pub is_running: bool,
     pub should_stop: bool,
     pub connected_clients: u32,
     pub shutdown_tx: tokio::sync::broadcast::Sender<()>,
+    pub auth_limiter: AuthLimiter,
+    // Per-session PIN overriding the configured one while set. Kept in
+    // plaintext only in memory so the launcher can show it and put it in the QR.
+    pub active_pin: Option<String>,
+    pub active_pin_hash: Option<PinHash>,
+    // Bumped on every PIN rotation; sessions remember the epoch they
+    // authenticated under
+    pub pin_epoch: u64,
+    // Carries the new epoch when sessions from older epochs must disconnect
+    pub pin_revoke_tx: tokio::sync::broadcast::Sender<u64>,
+    // One-time manual pairing code, if one is on screen
+    pub pairing_code: Option<IssuedPairingCode>,
+    // Config new connections use. Set by `run_server` and replaced when the
+    // settings are saved while it runs.
+    pub config: Arc<Config>,
+    // Where input goes. Built from the config by `run_server` unless an
+    // embedder installed one beforehand.
+    pub output: Option<Injector>,
+    pub players: PlayerSlots,
+}
+
+impl Default for ServerState {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl ServerState {
     pub fn new() -> Self {
         let (tx, _rx) = tokio::sync::broadcast::channel(1);
+        let (revoke_tx, _revoke_rx) = tokio::sync::broadcast::channel(4);
         Self {
             is_running: false,
             should_stop: false,
             connected_clients: 0,
             shutdown_tx: tx,
+            auth_limiter: AuthLimiter::new(),
+            active_pin: None,
+            active_pin_hash: None,
+            pin_epoch: 0,
+            pin_revoke_tx: revoke_tx,
+            pairing_code: None,
+            config: Arc::new(Config::default()),
+            output: None,
+            players: PlayerSlots::default(),
+        }
+    }
+
+    // Hash of the PIN clients must currently authenticate with
+    pub fn pin_hash(&self, config: &Config) -> Option<PinHash> {
+        self.active_pin_hash.clone().or_else(|| config.pin_hash.clone())
+    }
+
+    pub fn clear_active_pin(&mut self) {
+        self.active_pin = None;
+        self.active_pin_hash = None;
+    }
+
+    // Checks a manual pairing code. Codes are single use: redeemed, expired
+    // or repeatedly guessed codes are discarded.
+    pub fn redeem_pairing_code(&mut self, given: &str) -> bool {
+        let Some(issued) = self.pairing_code.as_mut() else {
+            return false;
+        };
+        let ok = issued.verify(given);
+        if ok || !issued.can_retry() {
+            self.pairing_code = None;
+        }
+        ok
+    }
+
+    // Uses the given PIN for this session instead of the configured one.
+    // Hash it first (`PinHash::new_blocking`), not while holding the lock.
+    // When `revoke` is set, sessions authenticated under any earlier PIN are
+    // disconnected.
+    pub fn set_active_pin(&mut self, pin: String, hash: PinHash, revoke: bool) {
+        self.active_pin_hash = Some(hash);
+        self.active_pin = Some(pin);
+        self.pin_epoch += 1;
+        if revoke {
+            let _ = self.pin_revoke_tx.send(self.pin_epoch);
         }
     }
 }
 
+// One message from the phone, borrowing from the frame it was parsed from so
+// button presses don't allocate. Identifiers never contain JSON escapes;
+// messages where they do are dropped like any other malformed one. The PIN
+// and device id are free-form and only sent once per connection, so they are
+// owned.
 #[derive(Debug, Deserialize)]
-struct ClientMessage {
+pub struct ClientMessage<'a> {
     #[serde(rename = "type")]
-    msg_type: String,
-    value: Option<String>,
-    key: Option<String>,
-    direction: Option<String>,
-    pressed: Option<bool>,
-    pin: Option<String>,
-    t: Option<i64>,
+    pub msg_type: &'a str,
+    pub value: Option<&'a str>,
+    pub key: Option<&'a str>,
+    pub direction: Option<&'a str>,
+    pub pressed: Option<bool>,
+    pub pin: Option<String>,
+    pub hmac: Option<&'a str>,
+    pub code: Option<&'a str>,
+    pub device_id: Option<String>,
+    // Analog value for "axis" messages, -1.0..=1.0
+    pub amount: Option<f32>,
+    pub t: Option<i64>,
+}
+
+impl ClientMessage<'_> {
+    // The control a button message refers to, whichever field the client
+    // put it in
+    pub fn value(&self) -> Option<&str> {
+        self.value.or(self.key).or(self.direction)
+    }
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Default, Serialize)]
 struct ServerResponse {
     #[serde(rename = "type")]
     msg_type: String,
@@ -51,23 +155,95 @@ struct ServerResponse {
     message: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     t: Option<i64>,
+    // Seconds the client must wait before retrying auth
+    #[serde(skip_serializing_if = "Option::is_none")]
+    retry_after: Option<u64>,
+    // Challenge the client must sign, and the PBKDF2 parameters it needs to
+    // derive the signing key from the PIN
+    #[serde(skip_serializing_if = "Option::is_none")]
+    nonce: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    salt: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    iterations: Option<u32>,
+    // 1-based player number, sent with auth_success
+    #[serde(skip_serializing_if = "Option::is_none")]
+    player: Option<u8>,
+    // Control the server let go of, sent with forced_release
+    #[serde(skip_serializing_if = "Option::is_none")]
+    action: Option<&'static str>,
+}
+
+// Input state of one authenticated connection
+struct Session {
+    player: u8,
+    macros: MacroRunner,
+    holds: HoldWatchdog,
+    repeat: AutoRepeat,
 }
 
 pub async fn run_server(
 // ...
     state: Arc<Mutex<ServerState>>,
-    app: AppHandle,
-    port: u16,
+    sink: Arc<dyn EventSink>,
+    config: Config,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let addr = format!("0.0.0.0:{}", port);
-    let listener = TcpListener::bind(&addr).await?;
+    let bind = BindTarget::from_config(&config)?;
+    {
+        let mut server = state.lock().await;
+        server.config = Arc::new(config.clone());
+        if server.output.is_none() {
+            server.output = Some(create_output(&config, &sink));
+        }
+    }
+    let addr = bind.socket_addr(config.port, config.dual_stack);
+    let listener = network::bind_listener(addr, config.dual_stack)?;
+    
+    sink.log(format!("WebSocket server listening on {}", addr));
     
-    let msg = format!("WebSocket server listening on {}", addr);
-    println!("{}", msg);
-    let _ = app.emit("log", msg);
+    let mdns = if config.mdns_enabled {
+        match MdnsAdvertiser::start(&config, bind) {
+            Ok(advertiser) => {
+                sink.log(format!("[OK] Advertising \"{}\" over mDNS", config.display_name()));
+                Some(advertiser)
+            }
+            Err(e) => {
+                sink.log(format!("[WARN] mDNS advertisement unavailable: {}", e));
+                None
+            }
+        }
+    } else {
+        None
+    };
+    
+    let (filter, invalid_rules) = NetworkFilter::from_config(&config);
+    for rule in invalid_rules {
+        sink.log(format!("[WARN] Ignoring invalid network rule: {}", rule));
+    }
+    
+    let udp_responder = if config.udp_discovery {
+        match discovery::bind_discovery_socket(bind, config.discovery_port).await {
+            Ok(socket) => {
+                sink.log(format!("[OK] Answering discovery probes on UDP port {}", config.discovery_port));
+                let reply = DiscoveryReply::new(&config, bind);
+                Some(tokio::spawn(discovery::run_udp_responder(
+                    socket,
+                    reply,
+                    filter.clone(),
+                    Arc::clone(&sink),
+                )))
+            }
+            Err(e) => {
+                sink.log(format!("[WARN] UDP discovery unavailable: {}", e));
+                None
+            }
+        }
+    } else {
+        None
+    };
     
     // Emit server started event
-    let _ = app.emit("server-status", "running");
+    sink.emit(ServerEvent::Status(ServerStatus::Running));
     
     loop {
         // Check if should stop
@@ -84,11 +260,18 @@ pub async fn run_server(
             listener.accept()
         ).await {
             Ok(Ok((stream, addr))) => {
+                // Reject before the handshake so blocked hosts get nothing back
+                if !filter.permits(addr.ip()) {
+                    sink.log(format!("[X] Rejected connection from {} (not an allowed network)", addr));
+                    drop(stream);
+                    continue;
+                }
+                
                 let state_clone = Arc::clone(&state);
-                let app_clone = app.clone();
+                let sink_clone = Arc::clone(&sink);
                 
                 tokio::spawn(async move {
-                    if let Err(e) = handle_connection(stream, addr, state_clone, app_clone).await {
+                    if let Err(e) = handle_connection(stream, addr, state_clone, sink_clone).await {
                         eprintln!("Connection error: {}", e);
                     }
                 });
@@ -102,8 +285,24 @@ pub async fn run_server(
         }
     }
     
-    let _ = app.emit("server-status", "stopped");
-    let _ = app.emit("log", "Server stopped");
+    if let Some(responder) = udp_responder {
+        responder.abort();
+    }
+    if let Some(advertiser) = mdns {
+        // Waits briefly for the goodbye packets to go out
+        let _ = tokio::task::spawn_blocking(move || advertiser.stop()).await;
+    }
+    
+    if let Some(output) = &state.lock().await.output {
+        let stats = output.stats();
+        sink.log(format!(
+            "Injected {} inputs (avg {}us, max {}us, {} failed)",
+            stats.injections, stats.avg_inject_us, stats.max_inject_us, stats.failures
+        ));
+    }
+    
+    sink.emit(ServerEvent::Status(ServerStatus::Stopped));
+    sink.log("Server stopped".to_string());
     Ok(())
 }
 
@@ -111,31 +310,51 @@ async fn handle_connection(
     stream: TcpStream,
     addr: SocketAddr,
     state: Arc<Mutex<ServerState>>,
-    app: AppHandle,
+    sink: Arc<dyn EventSink>,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     let ws_stream = accept_async(stream).await?;
     let (mut write, mut read) = ws_stream.split();
     
-    let msg = format!("[+] New connection from: {}", addr);
-    println!("{}", msg);
-    let _ = app.emit("log", msg);
+    sink.log(format!("[+] New connection from: {}", addr));
     
-    // Resolve config path
-    let config_path = match app.path().app_config_dir() {
-        Ok(dir) => dir.join("config.json"),
-        Err(_) => std::path::PathBuf::from("config.json"),
+    // Snapshot for the whole session; saved settings apply to new connections
+    let (config, output) = {
+        let server = state.lock().await;
+        (Arc::clone(&server.config), server.output.clone())
     };
-    
-    let config = config::load_config(Some(config_path));
-    let mut authenticated = false;
+    // Only missing while the launcher restarts the server and run_server has
+    // not installed the new backend yet
+    let Some(output) = output else {
+        let _ = write.send(Message::Close(None)).await;
+        sink.log(format!("[X] Output not ready, closing connection from {}", addr));
+        return Err("output backend not ready".into());
+    };
+    // Set once authenticated
+    let mut player_slot = None;
     
     // Update client count
     {
         let mut server = state.lock().await;
         server.connected_clients += 1;
-        let _ = app.emit("client-count", server.connected_clients);
+        sink.emit(ServerEvent::ClientCount(server.connected_clients));
     }
     
+    // Challenge-response: the client answers with an HMAC of the nonce keyed
+    // by the PIN, so the PIN itself never crosses the network
+    let (pin_hash, auth_epoch) = {
+        let server = state.lock().await;
+        (server.pin_hash(&config), server.pin_epoch)
+    };
+    let nonce = auth::generate_nonce();
+    let challenge = ServerResponse {
+        msg_type: "challenge".to_string(),
+        nonce: Some(nonce.clone()),
+        salt: pin_hash.as_ref().map(|h| h.salt.clone()),
+        iterations: pin_hash.as_ref().map(|h| h.iterations),
+        ..Default::default()
+    };
+    let _ = write.send(Message::Text(serde_json::to_string(&challenge)?)).await;
+    
     // Authentication timeout
     let auth_timeout = tokio::time::timeout(
         tokio::time::Duration::from_secs(10),
@@ -147,58 +366,116 @@ async fn handle_connection(
             if let Ok(text) = msg.to_text() {
                 if let Ok(data) = serde_json::from_str::<ClientMessage>(text) {
                     if data.msg_type == "auth" {
-                        if data.pin.as_deref() == Some(&config.pin) {
-                            authenticated = true;
+                        let keys = auth::limiter_keys(addr.ip());
+                        // Counted as failed until it succeeds, so parallel
+                        // connections can't all verify before the lockout
+                        let attempt = state.lock().await.auth_limiter.begin_attempt(&keys);
+                        let code_ok = match data.code {
+                            Some(code) if attempt.is_ok() => state.lock().await.redeem_pairing_code(code),
+                            _ => false,
+                        };
+                        // Locked out clients don't get to make us derive keys
+                        let pin_ok = attempt.is_ok() && (code_ok || match (pin_hash.as_ref(), data.hmac, data.pin.as_deref()) {
+                            (None, _, _) => false,
+                            (Some(hash), Some(mac), _) => hash.verify_response(&nonce, mac),
+                            (Some(hash), None, Some(given)) if config.legacy_pin_auth => hash.verify_blocking(given).await,
+                            (Some(_), None, Some(_)) => {
+                                sink.log(format!("[WARN] Plain PIN auth is disabled, rejecting: {}", addr));
+                                false
+                            }
+                            (Some(_), None, None) => false,
+                        });
+
+                        if let Err(wait) = attempt {
                             let response = ServerResponse {
-                                msg_type: "auth_success".to_string(),
-                                message: None,
-                                t: None,
+                                msg_type: "auth_failed".to_string(),
+                                message: Some("Too many failed attempts".to_string()),
+                                retry_after: Some(wait.as_secs().max(1)),
+                                ..Default::default()
                             };
                             let _ = write.send(Message::Text(serde_json::to_string(&response)?)).await;
-                            
-                            let log_msg = format!("[OK] Authenticated: {}", addr);
-                            println!("{}", log_msg);
-                            let _ = app.emit("log", log_msg);
-                            let _ = app.emit("client-authenticated", addr.to_string());
+
+                            sink.log(format!("[LOCKOUT] Rejected auth from {} ({}s remaining)", addr, wait.as_secs().max(1)));
+                        } else if pin_ok {
+                            player_slot = {
+                                let mut server = state.lock().await;
+                                server.auth_limiter.record_success(&keys);
+                                server.players.claim()
+                            };
+                            if let Some(slot) = player_slot {
+                                let response = ServerResponse {
+                                    msg_type: "auth_success".to_string(),
+                                    message: None,
+                                    player: Some(slot + 1),
+                                    ..Default::default()
+                                };
+                                let _ = write.send(Message::Text(serde_json::to_string(&response)?)).await;
+
+                                sink.log(format!("[OK] Authenticated: {}", addr));
+                                sink.emit(ServerEvent::ClientAuthenticated(addr));
+                            } else {
+                                // Sharing a slot would let one client's
+                                // disconnect release another's keys
+                                let response = ServerResponse {
+                                    msg_type: "auth_failed".to_string(),
+                                    message: Some(format!("All {} player slots are taken", MAX_PLAYERS)),
+                                    ..Default::default()
+                                };
+                                let _ = write.send(Message::Text(serde_json::to_string(&response)?)).await;
+
+                                sink.log(format!("[X] Rejected {}: all {} player slots are taken", addr, MAX_PLAYERS));
+                            }
                         } else {
+                            let lockout = attempt.ok().flatten();
                             let response = ServerResponse {
                                 msg_type: "auth_failed".to_string(),
                                 message: Some("Invalid PIN".to_string()),
-                                t: None,
+                                retry_after: lockout.map(|d| d.as_secs()),
+                                ..Default::default()
                             };
                             let _ = write.send(Message::Text(serde_json::to_string(&response)?)).await;
                             
-                            let log_msg = format!("[X] Auth failed: {}", addr);
-                            println!("{}", log_msg);
-                            let _ = app.emit("log", log_msg);
+                            sink.log(format!("[X] Auth failed: {}", addr));
+
+                            if let Some(duration) = lockout {
+                                sink.log(format!("[LOCKOUT] {} locked out for {}s", addr, duration.as_secs()));
+                            }
                         }
                     }
                 }
             }
         }
         _ => {
-            let log_msg = format!("[TIMEOUT] Auth timeout: {}", addr);
-            println!("{}", log_msg);
-            let _ = app.emit("log", log_msg);
+            sink.log(format!("[TIMEOUT] Auth timeout: {}", addr));
         }
     }
     
-    if !authenticated {
+    let Some(player) = player_slot else {
         // Cleanup
         let mut server = state.lock().await;
         server.connected_clients = server.connected_clients.saturating_sub(1);
-        let _ = app.emit("client-count", server.connected_clients);
+        sink.emit(ServerEvent::ClientCount(server.connected_clients));
         return Ok(());
-    }
+    };
     
-    // Get shutdown receiver
-    let mut shutdown_rx = {
+    output.connect(player);
+    let mut session = Session {
+        player,
+        macros: MacroRunner::new(output.clone(), player, &config, Arc::clone(&sink)),
+        holds: HoldWatchdog::new(&config),
+        repeat: AutoRepeat::new(&config.auto_repeat),
+    };
+    let mut watchdog_tick = tokio::time::interval(watchdog::CHECK_INTERVAL);
+    
+    // Get shutdown and PIN revocation receivers
+    let (mut shutdown_rx, mut revoke_rx) = {
         let server = state.lock().await;
-        server.shutdown_tx.subscribe()
+        (server.shutdown_tx.subscribe(), server.pin_revoke_tx.subscribe())
     };
     
     // Main message loop
     loop {
+        let repeat_due = session.repeat.next_due();
         tokio::select! {
             msg_result = read.next() => {
                 match msg_result {
@@ -209,7 +486,7 @@ async fn handle_connection(
                         
                         if let Ok(text) = msg.to_text() {
                             if let Ok(data) = serde_json::from_str::<ClientMessage>(text) {
-                                handle_input(&data, &config, &mut write, &app).await;
+                                handle_input(&data, &config, &output, &mut session, &mut write, sink.as_ref()).await;
                             }
                         }
                     }
@@ -222,115 +499,406 @@ async fn handle_connection(
                     }
                 }
             }
+            _ = sleep_until(repeat_due), if repeat_due.is_some() => {
+                // Backends count holds per key or button, so while another
+                // held action shares the output (strum up and nav up are both
+                // Up by default) this release and press change nothing
+                for action in session.repeat.due(std::time::Instant::now()) {
+                    output.release(player, action);
+                    output.press(player, action);
+                }
+            }
+            _ = watchdog_tick.tick() => {
+                let now = std::time::Instant::now();
+                for (action, held) in session.holds.expired(now) {
+                    session.repeat.released(action);
+                    session.macros.client_release(action);
+                    let response = ServerResponse {
+                        msg_type: "forced_release".to_string(),
+                        action: Some(action.name()),
+                        ..Default::default()
+                    };
+                    let _ = write.send(Message::Text(serde_json::to_string(&response)?)).await;
+
+                    sink.log(format!("[WARN] Released {} for {} after {:.1}s without a release", action.name(), addr, held.as_secs_f32()));
+                    sink.emit(ServerEvent::ForcedRelease(addr, action));
+                }
+                for (action, held) in session.macros.expired(now) {
+                    sink.log(format!("[WARN] Released {} held by a macro for {} after {:.1}s", action.name(), addr, held.as_secs_f32()));
+                }
+            }
             _ = shutdown_rx.recv() => {
                 println!("Closing connection due to server stop: {}", addr);
                 break;
             }
+            Ok(epoch) = revoke_rx.recv() => {
+                if auth_epoch < epoch {
+                    let response = ServerResponse {
+                        msg_type: "session_revoked".to_string(),
+                        message: Some("PIN changed".to_string()),
+                        ..Default::default()
+                    };
+                    let _ = write.send(Message::Text(serde_json::to_string(&response)?)).await;
+
+                    sink.log(format!("[X] Session revoked after PIN rotation: {}", addr));
+                    break;
+                }
+            }
         }
     }
     
-    // Release all keys on disconnect
-    keyboard::release_all(&config);
+    // Release all keys on disconnect: first whatever the client still held,
+    // so backends that only track their own state let go too
+    session.macros.cancel();
+    let held: Vec<Action> = session.holds.held(std::time::Instant::now()).map(|(action, _)| action).collect();
+    for action in held {
+        output.release(player, action);
+    }
+    output.release_all(player);
     
     // Update client count
     {
         let mut server = state.lock().await;
+        server.players.free(player);
         server.connected_clients = server.connected_clients.saturating_sub(1);
-        let _ = app.emit("client-count", server.connected_clients);
+        sink.emit(ServerEvent::ClientCount(server.connected_clients));
     }
     
-    let log_msg = format!("[DISCONNECTED] {}", addr);
-    println!("{}", log_msg);
-    let _ = app.emit("log", log_msg);
-    let _ = app.emit("client-disconnected", addr.to_string());
+    sink.log(format!("[DISCONNECTED] {}", addr));
+    sink.emit(ServerEvent::ClientDisconnected(addr));
     
     Ok(())
 }
 
 async fn handle_input<S>(
-    data: &ClientMessage,
+    data: &ClientMessage<'_>,
     config: &Config,
+    output: &Injector,
+    session: &mut Session,
     write: &mut S,
-    app: &AppHandle,
+    sink: &dyn EventSink,
 ) where
     S: SinkExt<Message> + Unpin,
     S::Error: std::fmt::Debug,
 {
     let pressed = data.pressed.unwrap_or(false);
-    let value = data.value.as_deref()
-        .or(data.key.as_deref())
-        .or(data.direction.as_deref());
+    let value = data.value();
     
-    match data.msg_type.as_str() {
+    match data.msg_type {
         "ping" => {
             let response = ServerResponse {
                 msg_type: "pong".to_string(),
                 message: None,
                 t: data.t,
+                ..Default::default()
             };
             let _ = write.send(Message::Text(serde_json::to_string(&response).unwrap())).await;
         }
         
-        "fret" => {
-            if let Some(fret) = value {
-                if ["green", "red", "yellow", "blue", "orange"].contains(&fret) {
-                    if pressed {
-                        keyboard::press_key(fret, config);
-                        let _ = app.emit("log", format!("[FRET] {} pressed", fret));
-                    } else {
-                        keyboard::release_key(fret, config);
-                        let _ = app.emit("log", format!("[FRET] {} released", fret));
-                    }
-                }
+        // Analog controls, e.g. {"type":"axis","value":"whammy","amount":0.8}
+        "axis" => {
+            if let (Some(action), Some(amount)) = (value.and_then(Action::from_axis), data.amount) {
+                output.axis(session.player, action, amount.clamp(-1.0, 1.0));
             }
         }
         
-        "strum" => {
-            if let Some(direction) = value {
-                let key = format!("strum_{}", direction);
-                if pressed {
-                    keyboard::press_key(&key, config);
-                    let _ = app.emit("log", format!("[STRUM] {}", direction));
-                } else {
-                    keyboard::release_key(&key, config);
+        // {"type":"macro","value":"quickplay"} runs `Config.macros["quickplay"]`
+        "macro" => {
+            if let Some(name) = value {
+                if let Err(e) = session.macros.start(name, config) {
+                    sink.log(format!("[WARN] {}", e));
                 }
             }
         }
         
-        "drum" => {
-            if let Some(pad) = value {
-                let key = if pad == "kick" {
-                    "drum_kick".to_string()
-                } else {
-                    format!("drum_{}", pad)
-                };
-                if pressed {
-                    keyboard::press_key(&key, config);
-                    let _ = app.emit("log", format!("[DRUM] {} hit", pad));
-                } else {
-                    keyboard::release_key(&key, config);
-                }
-            }
+        "macro_cancel" => {
+            session.macros.cancel();
         }
         
-        "starpower" | "whammy" | "start" | "select" => {
+        msg_type => {
+            let Some(action) = Action::from_message(msg_type, value) else {
+                return;
+            };
             if pressed {
-                keyboard::press_key(&data.msg_type, config);
-                let _ = app.emit("log", format!("[ACTION] {} pressed", data.msg_type));
+                let now = std::time::Instant::now();
+                session.macros.client_press(action);
+                session.holds.pressed(action, now);
+                session.repeat.pressed(action, now);
             } else {
-                keyboard::release_key(&data.msg_type, config);
+                session.macros.client_release(action);
+                session.holds.released(action);
+                session.repeat.released(action);
+            }
+            if !sink.wants_input_logs() {
+                return;
+            }
+            match (action.group(), pressed) {
+                (ActionGroup::Fret, true) => sink.log(format!("[FRET] {} pressed", action.name())),
+                (ActionGroup::Fret, false) => sink.log(format!("[FRET] {} released", action.name())),
+                (ActionGroup::Strum, true) => sink.log(format!("[STRUM] {}", action.short_name())),
+                (ActionGroup::Drum, true) => sink.log(format!("[DRUM] {} hit", action.short_name())),
+                (ActionGroup::Action, true) => sink.log(format!("[ACTION] {} pressed", action.name())),
+                (ActionGroup::Nav, true) => sink.log(format!("[NAV] {} pressed", action.name())),
+                _ => {}
             }
         }
-        
-        "left" | "right" | "up" | "down" => {
-            if pressed {
-                keyboard::press_key(&data.msg_type, config);
-                let _ = app.emit("log", format!("[NAV] {} pressed", data.msg_type));
-            } else {
-                keyboard::release_key(&data.msg_type, config);
+    }
+}
+
+// Waits for an optional deadline; guarded by an `if` in `select!` when None
+async fn sleep_until(deadline: Option<std::time::Instant>) {
+    if let Some(deadline) = deadline {
+        tokio::time::sleep_until(deadline.into()).await;
+    }
+}
+
+// The configured backend, or a recording one (dry run) if it can't start,
+// e.g. without a display
+fn create_output(config: &Config, sink: &Arc<dyn EventSink>) -> Injector {
+    if config.output_backend == OutputBackendKind::Keyboard {
+        let (_, unknown) = BindingTable::resolve(config, keys::parse_chord);
+        for binding in unknown {
+            sink.log(format!("[WARN] Unknown key in binding {}", binding));
+        }
+    }
+    let backend = output::create(config).unwrap_or_else(|e| {
+        sink.log(format!("[WARN] Output unavailable, input will not reach the OS: {}", e));
+        Box::new(RecordingOutput::new())
+    });
+    Injector::spawn(backend, Arc::clone(sink))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::RecordingSink;
+    use crate::output::OutputEvent;
+    use hmac::{Hmac, Mac};
+    use std::time::Duration;
+    use tokio::task::JoinHandle;
+    use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+
+    type Client = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+    // HMAC key the test clients sign with; only the handshake reads it
+    const KEY: [u8; 32] = [0x11; 32];
+
+    struct TestServer {
+        port: u16,
+        state: Arc<Mutex<ServerState>>,
+        output: RecordingOutput,
+        sink: Arc<RecordingSink>,
+        task: JoinHandle<Result<(), Box<dyn std::error::Error + Send + Sync>>>,
+    }
+
+    impl TestServer {
+        async fn start() -> Self {
+            // run_server binds the configured port, so reserve a free one first
+            let port = std::net::TcpListener::bind("127.0.0.1:0")
+                .unwrap()
+                .local_addr()
+                .unwrap()
+                .port();
+            let config = Config {
+                port,
+                bind_address: "loopback".to_string(),
+                pin: String::new(),
+                pin_hash: Some(PinHash {
+                    salt: "00".repeat(16),
+                    iterations: 1,
+                    hash: hex::encode(KEY),
+                }),
+                mdns_enabled: false,
+                ..Default::default()
+            };
+
+            let output = RecordingOutput::new();
+            let sink = Arc::new(RecordingSink::new());
+            let state = Arc::new(Mutex::new(ServerState::new()));
+            {
+                let mut server = state.lock().await;
+                server.is_running = true;
+                server.output = Some(Injector::spawn(Box::new(output.clone()), sink.clone()));
             }
+            let task = tokio::spawn(run_server(Arc::clone(&state), sink.clone(), config));
+            wait_until(|| sink.events().contains(&ServerEvent::Status(ServerStatus::Running))).await;
+
+            Self { port, state, output, sink, task }
         }
-        
-        _ => {}
+
+        async fn connect(&self) -> (Client, String) {
+            let (mut ws, _) = connect_async(format!("ws://127.0.0.1:{}", self.port)).await.unwrap();
+            let challenge = recv(&mut ws).await;
+            assert_eq!(challenge["type"], "challenge");
+            let nonce = challenge["nonce"].as_str().unwrap().to_string();
+            (ws, nonce)
+        }
+
+        async fn stop(self) {
+            {
+                let mut server = self.state.lock().await;
+                server.should_stop = true;
+                let _ = server.shutdown_tx.send(());
+            }
+            tokio::time::timeout(Duration::from_secs(5), self.task)
+                .await
+                .expect("server did not stop")
+                .unwrap()
+                .unwrap();
+        }
+    }
+
+    fn sign(nonce: &str) -> String {
+        let mut mac = Hmac::<sha2::Sha256>::new_from_slice(&KEY).unwrap();
+        mac.update(nonce.as_bytes());
+        hex::encode(mac.finalize().into_bytes())
+    }
+
+    async fn send(ws: &mut Client, json: &str) {
+        ws.send(Message::Text(json.to_string())).await.unwrap();
+    }
+
+    async fn recv(ws: &mut Client) -> serde_json::Value {
+        let msg = tokio::time::timeout(Duration::from_secs(5), ws.next())
+            .await
+            .expect("no message from the server")
+            .unwrap()
+            .unwrap();
+        serde_json::from_str(msg.to_text().unwrap()).unwrap()
+    }
+
+    // Output goes through the injector thread, so events land a little later
+    async fn wait_until(condition: impl Fn() -> bool) {
+        for _ in 0..200 {
+            if condition() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("condition not met in time");
+    }
+
+    #[test]
+    fn parses_client_message() {
+        let frame = r#"{"type":"strum","direction":"up","pressed":true,"pin":"12\"34","device_id":"phone"}"#;
+        let message: ClientMessage = serde_json::from_str(frame).unwrap();
+        assert_eq!(Action::from_message(message.msg_type, message.value()), Some(Action::StrumUp));
+        assert_eq!(message.pressed, Some(true));
+        assert_eq!(message.pin.as_deref(), Some("12\"34"));
+        assert_eq!(message.device_id.as_deref(), Some("phone"));
+
+        // Identifiers borrow, so escapes in them make the frame malformed
+        assert!(serde_json::from_str::<ClientMessage>(r#"{"type":"fr\u0065t"}"#).is_err());
+    }
+
+    #[tokio::test]
+    async fn plays_and_releases_on_disconnect() {
+        let server = TestServer::start().await;
+        let (mut ws, nonce) = server.connect().await;
+
+        send(&mut ws, &format!(r#"{{"type":"auth","hmac":"{}"}}"#, sign(&nonce))).await;
+        let reply = recv(&mut ws).await;
+        assert_eq!(reply["type"], "auth_success");
+        assert_eq!(reply["player"], 1);
+
+        send(&mut ws, r#"{"type":"ping","t":42}"#).await;
+        let pong = recv(&mut ws).await;
+        assert_eq!((pong["type"].as_str(), pong["t"].as_i64()), (Some("pong"), Some(42)));
+
+        send(&mut ws, r#"{"type":"fret","value":"green","pressed":true}"#).await;
+        send(&mut ws, r#"{"type":"strum","value":"down","pressed":true}"#).await;
+        send(&mut ws, r#"{"type":"strum","value":"down","pressed":false}"#).await;
+        send(&mut ws, r#"{"type":"bogus","pressed":true}"#).await;
+        ws.close(None).await.unwrap();
+
+        let disconnected = |sink: &RecordingSink| {
+            sink.events().iter().any(|e| matches!(e, ServerEvent::ClientDisconnected(_)))
+        };
+        wait_until(|| disconnected(&server.sink)).await;
+        wait_until(|| server.output.events().contains(&OutputEvent::ReleaseAll(0))).await;
+        assert_eq!(
+            server.output.events(),
+            [
+                OutputEvent::Connect(0),
+                OutputEvent::Press(0, Action::Green),
+                OutputEvent::Press(0, Action::StrumDown),
+                OutputEvent::Release(0, Action::StrumDown),
+                OutputEvent::Release(0, Action::Green),
+                OutputEvent::ReleaseAll(0),
+            ]
+        );
+
+        let logs = server.sink.logs();
+        assert!(logs.iter().any(|l| l == "[FRET] green pressed"));
+        assert!(logs.iter().any(|l| l == "[STRUM] down"));
+        assert!(server.sink.events().iter().any(|e| matches!(e, ServerEvent::ClientAuthenticated(_))));
+        server.stop().await;
+    }
+
+    #[tokio::test]
+    async fn closes_connections_before_output_is_ready() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let sink = Arc::new(RecordingSink::new());
+        let state = Arc::new(Mutex::new(ServerState::new()));
+        let server = {
+            let sink = sink.clone();
+            tokio::spawn(async move {
+                let (stream, addr) = listener.accept().await.unwrap();
+                handle_connection(stream, addr, state, sink).await
+            })
+        };
+
+        let (mut ws, _) = connect_async(format!("ws://127.0.0.1:{}", port)).await.unwrap();
+        let msg = tokio::time::timeout(Duration::from_secs(5), ws.next()).await.unwrap();
+        assert!(matches!(msg, Some(Ok(Message::Close(_))) | None));
+        assert!(server.await.unwrap().is_err());
+        assert!(sink.logs().iter().any(|l| l.starts_with("[X] Output not ready")));
+    }
+
+    #[tokio::test]
+    async fn rejects_clients_once_every_slot_is_taken() {
+        let server = TestServer::start().await;
+        let mut clients = Vec::new();
+        for player in 1..=MAX_PLAYERS {
+            let (mut ws, nonce) = server.connect().await;
+            send(&mut ws, &format!(r#"{{"type":"auth","hmac":"{}"}}"#, sign(&nonce))).await;
+            let reply = recv(&mut ws).await;
+            assert_eq!((reply["type"].as_str(), reply["player"].as_u64()), (Some("auth_success"), Some(player as u64)));
+            clients.push(ws);
+        }
+
+        let (mut ws, nonce) = server.connect().await;
+        send(&mut ws, &format!(r#"{{"type":"auth","hmac":"{}"}}"#, sign(&nonce))).await;
+        let reply = recv(&mut ws).await;
+        assert_eq!(reply["type"], "auth_failed");
+        assert_eq!(reply["message"], "All 8 player slots are taken");
+
+        // A freed slot is handed out again
+        clients.remove(2).close(None).await.unwrap();
+        wait_until(|| server.output.events().contains(&OutputEvent::ReleaseAll(2))).await;
+        let (mut ws, nonce) = server.connect().await;
+        send(&mut ws, &format!(r#"{{"type":"auth","hmac":"{}"}}"#, sign(&nonce))).await;
+        assert_eq!(recv(&mut ws).await["player"], 3);
+        server.stop().await;
+    }
+
+    #[tokio::test]
+    async fn rejects_bad_signature() {
+        let server = TestServer::start().await;
+        let (mut ws, _) = server.connect().await;
+
+        send(&mut ws, &format!(r#"{{"type":"auth","hmac":"{}"}}"#, sign("some other nonce"))).await;
+        let reply = recv(&mut ws).await;
+        assert_eq!(reply["type"], "auth_failed");
+
+        // Plain PINs are refused unless legacy auth is on
+        let (mut ws, _) = server.connect().await;
+        send(&mut ws, r#"{"type":"auth","pin":"1234"}"#).await;
+        assert_eq!(recv(&mut ws).await["type"], "auth_failed");
+
+        assert!(server.output.events().is_empty());
+        assert!(server.sink.logs().iter().any(|l| l.starts_with("[WARN] Plain PIN auth is disabled")));
+        server.stop().await;
     }
 }
@@ -12,6 +12,27 @@ interface Config {
   port: number;
   pin: string;
   key_bindings: Record<string, string>;
+  legacy_pin_auth: boolean;
+  randomize_pin: boolean;
+  bind_address: string;
+  dual_stack: boolean;
+  allowed_networks: string[];
+  denied_networks: string[];
+  server_name: string;
+  mdns_enabled: boolean;
+  udp_discovery: boolean;
+  discovery_port: number;
+  output_backend: "keyboard" | "recording" | "gamepad";
+  // Steps like { tap: "down" } or { wait: 150 }, edited in config.json
+  macros: Record<string, Record<string, string | number>[]>;
+  max_hold_ms: Record<string, number>;
+  auto_repeat: {
+    enabled: boolean;
+    delay_ms: number;
+    interval_ms: number;
+    acceleration: number;
+    min_interval_ms: number;
+  };
 }
 
 function App() {
@@ -21,6 +42,8 @@ function App() {
   const [config, setConfig] = useState<Config | null>(null);
   const [showSettings, setShowSettings] = useState(false);
   const [localIp, setLocalIp] = useState("");
+  const [activePin, setActivePin] = useState("");
+  const [pairingCode, setPairingCode] = useState("");
 
   useEffect(() => {
     // Initialize
@@ -42,12 +65,14 @@ function App() {
     };
   }, []);
 
-  const generateQr = async (ip: string, port: number, pin: string) => {
+  const refreshPairing = async () => {
     try {
-      const qr = await invoke<string>("generate_qr_code", {
-        data: `mobilehero://${ip}:${port}?pin=${pin}`,
+      // The pairing URI format is defined (and versioned) by the launcher.
+      // SVG stays sharp however the sidebar scales it.
+      const pairing = await invoke<{ uri: string; qr: string }>("get_pairing_info", {
+        options: { format: "svg", error_correction: "M" },
       });
-      setQrCode(qr);
+      setQrCode(`data:image/svg+xml;charset=utf-8,${encodeURIComponent(pairing.qr)}`);
     } catch (error) {
       console.error("QR Error:", error);
     }
@@ -56,18 +81,48 @@ function App() {
   const startServer = async () => {
     try {
       if (config) {
-        await invoke("start_server");
-        await generateQr(localIp, config.port, config.pin);
+        // Returns "ip:port" (IPv6 bracketed) for the configured bind address
+        const address = await invoke<string>("start_server");
+        const ip = address.slice(0, address.lastIndexOf(":"));
+        setLocalIp(ip);
+        const pin = await invoke<string | null>("get_active_pin");
+        setActivePin(pin || "");
+        await refreshPairing();
       }
     } catch (error) {
       console.error("Start Error:", error);
     }
   };
 
+  const rotatePin = async () => {
+    try {
+      if (config) {
+        const disconnectExisting = window.confirm("Disconnect phones paired with the old PIN?");
+        const pin = await invoke<string>("rotate_pin", { disconnectExisting });
+        setActivePin(pin);
+        await refreshPairing();
+      }
+    } catch (error) {
+      console.error("Rotate Error:", error);
+    }
+  };
+
+  const showPairingCode = async () => {
+    try {
+      const issued = await invoke<{ code: string; expires_in: number }>("generate_pairing_code");
+      setPairingCode(issued.code);
+      // Codes are single use and expire on the server; stop showing stale ones
+      setTimeout(() => setPairingCode((current) => (current === issued.code ? "" : current)), issued.expires_in * 1000);
+    } catch (error) {
+      console.error("Pairing Code Error:", error);
+    }
+  };
+
   const stopServer = async () => {
     try {
       await invoke("stop_server");
       setQrCode("");
+      setPairingCode("");
       setClientCount(0);
     } catch (error) {
       console.error("Stop Error:", error);
@@ -77,11 +132,13 @@ function App() {
   const saveSettings = async (newConfig: Config) => {
     try {
       await invoke("save_config", { config: newConfig });
-      setConfig(newConfig);
+      setConfig({ ...newConfig, pin: "" });
       setShowSettings(false);
       // Regenerate QR if pin changed and server is running
       if (serverRunning) {
-        generateQr(localIp, newConfig.port, newConfig.pin);
+        const pin = await invoke<string | null>("get_active_pin");
+        setActivePin(pin || "");
+        refreshPairing();
       }
     } catch (error) {
       console.error("Save Error:", error);
@@ -98,10 +155,13 @@ function App() {
           clientCount={clientCount}
           ip={localIp}
           port={config?.port || 8080}
-          pin={config?.pin || "1234"}
+          pin={activePin || "••••"}
           qrCode={qrCode}
+          pairingCode={pairingCode}
           onStart={startServer}
           onStop={stopServer}
+          onRotatePin={rotatePin}
+          onPairingCode={showPairingCode}
           onSettings={() => setShowSettings(true)}
         />
 
@@ -12,7 +12,7 @@ export default function Console() {
 
             let type = 'norm';
             if (msg.includes('[ERROR]') || msg.includes('[X]')) type = 'log-e';
-            else if (msg.includes('[WARN]')) type = 'log-w';
+            else if (msg.includes('[WARN]') || msg.includes('[LOCKOUT]')) type = 'log-w';
             else if (msg.includes('[+]') || msg.includes('[OK]')) type = 'log-i';
 
             setLogs((prev) => [...prev.slice(-100), { time, msg, type }]);
@@ -1,9 +1,37 @@
-import { useState, useEffect } from 'react';
+import { useState, useEffect, type KeyboardEvent } from 'react';
+import { invoke } from '@tauri-apps/api/core';
 
 interface Config {
     port: number;
     pin: string;
     key_bindings: Record<string, string>;
+    legacy_pin_auth: boolean;
+    randomize_pin: boolean;
+    bind_address: string;
+    dual_stack: boolean;
+    allowed_networks: string[];
+    denied_networks: string[];
+    server_name: string;
+    mdns_enabled: boolean;
+    udp_discovery: boolean;
+    discovery_port: number;
+    output_backend: "keyboard" | "recording" | "gamepad";
+    // Steps like { tap: "down" } or { wait: 150 }, edited in config.json
+    macros: Record<string, Record<string, string | number>[]>;
+    max_hold_ms: Record<string, number>;
+    auto_repeat: {
+        enabled: boolean;
+        delay_ms: number;
+        interval_ms: number;
+        acceleration: number;
+        min_interval_ms: number;
+    };
+}
+
+interface KeyGroup {
+    category: string;
+    label: string;
+    keys: { name: string; aliases: string[] }[];
 }
 
 interface Props {
@@ -19,8 +47,42 @@ const INSTRUMENTS = {
     'Drums': ['drum_red', 'drum_yellow', 'drum_blue', 'drum_orange', 'drum_green', 'drum_kick']
 };
 
+// Numpad keys and right-hand modifiers share `key` with other keys, so use
+// their physical `code` ("Numpad0", "ShiftRight") instead
+const RIGHT_MODIFIERS = ['ShiftRight', 'ControlRight', 'AltRight'];
+const keyName = (e: KeyboardEvent) =>
+    e.code.startsWith('Numpad') || RIGHT_MODIFIERS.includes(e.code) ? e.code : e.key;
+
+// Keys pressed with held modifiers become a chord, e.g. "Ctrl+Shift+F1".
+// Shift changes `key` ("!" for 1), so name letters and digits by `code`.
+const MODIFIER_KEYS = ['Control', 'Shift', 'Alt', 'Meta'];
+
+// Physical bindings name the key's position on US QWERTY ("KeyQ" -> "phys:q"),
+// so they press the same key whatever layout the PC uses
+const PHYSICAL_PREFIX = 'phys:';
+
+const bindingName = (e: KeyboardEvent, physical: boolean) => {
+    const modifiers = [
+        e.ctrlKey && 'Ctrl',
+        e.shiftKey && 'Shift',
+        e.altKey && 'Alt',
+        e.metaKey && 'Meta',
+    ].filter(Boolean);
+    const chord = modifiers.length > 0 && !MODIFIER_KEYS.includes(e.key);
+    const name = e.code.startsWith('Key') && (physical || chord) ? e.code.slice(3).toLowerCase()
+        : e.code.startsWith('Digit') && chord ? e.code.slice(5)
+        : physical ? e.code
+        : keyName(e);
+    return (physical ? PHYSICAL_PREFIX : '') + (chord ? [...modifiers, name].join('+') : name);
+};
+
 export default function SettingsModal({ isOpen, onClose, config, onSave }: Props) {
     const [localConfig, setLocalConfig] = useState<Config | null>(config);
+    const [keyGroups, setKeyGroups] = useState<KeyGroup[]>([]);
+
+    useEffect(() => {
+        invoke<KeyGroup[]>('list_key_names').then(setKeyGroups);
+    }, []);
 
     useEffect(() => {
         if (config) {
@@ -30,6 +92,8 @@ export default function SettingsModal({ isOpen, onClose, config, onSave }: Props
 
     if (!isOpen || !localConfig) return null;
 
+    const parseList = (value: string) => value.split(',').map((s) => s.trim());
+
     const handleKeyChange = (key: string, value: string) => {
         setLocalConfig({
             ...localConfig,
@@ -40,6 +104,13 @@ export default function SettingsModal({ isOpen, onClose, config, onSave }: Props
         });
     };
 
+    const setRepeat = (changes: Partial<Config['auto_repeat']>) => {
+        setLocalConfig({
+            ...localConfig,
+            auto_repeat: { ...localConfig.auto_repeat, ...changes }
+        });
+    };
+
     return (
         <div className="modal-overlay">
             <div className="modal-content">
@@ -60,37 +131,186 @@ export default function SettingsModal({ isOpen, onClose, config, onSave }: Props
                                 onChange={(e) => setLocalConfig({ ...localConfig, port: parseInt(e.target.value) })}
                             />
                         </div>
+                        <div className="input-row">
+                            <span className="input-label">PC Name</span>
+                            <input
+                                className="input-field"
+                                placeholder="Hostname"
+                                value={localConfig.server_name}
+                                onChange={(e) => setLocalConfig({ ...localConfig, server_name: e.target.value })}
+                            />
+                        </div>
+                        <div className="input-row">
+                            <span className="input-label">Auto Discovery (mDNS)</span>
+                            <input
+                                type="checkbox"
+                                checked={localConfig.mdns_enabled}
+                                onChange={(e) => setLocalConfig({ ...localConfig, mdns_enabled: e.target.checked })}
+                            />
+                        </div>
+                        <div className="input-row">
+                            <span className="input-label">Broadcast Discovery (UDP)</span>
+                            <input
+                                type="checkbox"
+                                checked={localConfig.udp_discovery}
+                                onChange={(e) => setLocalConfig({ ...localConfig, udp_discovery: e.target.checked })}
+                            />
+                        </div>
+                        <div className="input-row">
+                            <span className="input-label">Output</span>
+                            <select
+                                className="input-field"
+                                value={localConfig.output_backend}
+                                onChange={(e) => setLocalConfig({ ...localConfig, output_backend: e.target.value as Config["output_backend"] })}
+                            >
+                                <option value="keyboard">Keyboard</option>
+                                <option value="gamepad">Virtual Gamepads (Linux)</option>
+                                <option value="recording">Dry Run (no input)</option>
+                            </select>
+                        </div>
+                        <div className="input-row">
+                            <span className="input-label">Listen On</span>
+                            <input
+                                className="input-field"
+                                placeholder="all, loopback or an IP"
+                                value={localConfig.bind_address}
+                                onChange={(e) => setLocalConfig({ ...localConfig, bind_address: e.target.value })}
+                            />
+                        </div>
+                        <div className="input-row">
+                            <span className="input-label">IPv6 Dual-Stack</span>
+                            <input
+                                type="checkbox"
+                                checked={localConfig.dual_stack}
+                                onChange={(e) => setLocalConfig({ ...localConfig, dual_stack: e.target.checked })}
+                            />
+                        </div>
                         <div className="input-row">
                             <span className="input-label">Security PIN</span>
                             <input
                                 className="input-field"
                                 maxLength={4}
+                                placeholder="Unchanged"
                                 value={localConfig.pin}
                                 onChange={(e) => setLocalConfig({ ...localConfig, pin: e.target.value })}
                             />
                         </div>
+                        <div className="input-row">
+                            <span className="input-label">Random PIN Each Session</span>
+                            <input
+                                type="checkbox"
+                                checked={localConfig.randomize_pin}
+                                onChange={(e) => setLocalConfig({ ...localConfig, randomize_pin: e.target.checked })}
+                            />
+                        </div>
+                        <div className="input-row">
+                            <span className="input-label">Allow Legacy PIN Login</span>
+                            <input
+                                type="checkbox"
+                                checked={localConfig.legacy_pin_auth}
+                                onChange={(e) => setLocalConfig({ ...localConfig, legacy_pin_auth: e.target.checked })}
+                            />
+                        </div>
+                        <div className="input-row">
+                            <span className="input-label">Allowed Networks</span>
+                            <input
+                                className="input-field"
+                                placeholder="Any"
+                                value={localConfig.allowed_networks.join(', ')}
+                                onChange={(e) => setLocalConfig({ ...localConfig, allowed_networks: parseList(e.target.value) })}
+                            />
+                        </div>
+                        <div className="input-row">
+                            <span className="input-label">Blocked Networks</span>
+                            <input
+                                className="input-field"
+                                placeholder="None"
+                                value={localConfig.denied_networks.join(', ')}
+                                onChange={(e) => setLocalConfig({ ...localConfig, denied_networks: parseList(e.target.value) })}
+                            />
+                        </div>
+                    </div>
+
+                    <div className="setting-group">
+                        <div className="group-title">Navigation Repeat</div>
+                        <div className="input-row">
+                            <span className="input-label">Repeat Held Buttons</span>
+                            <input
+                                type="checkbox"
+                                checked={localConfig.auto_repeat.enabled}
+                                onChange={(e) => setRepeat({ enabled: e.target.checked })}
+                            />
+                        </div>
+                        <div className="input-row">
+                            <span className="input-label">Delay (ms)</span>
+                            <input
+                                className="input-field"
+                                type="number"
+                                min={0}
+                                value={localConfig.auto_repeat.delay_ms}
+                                onChange={(e) => setRepeat({ delay_ms: parseInt(e.target.value) })}
+                            />
+                        </div>
+                        <div className="input-row">
+                            <span className="input-label">Interval (ms)</span>
+                            <input
+                                className="input-field"
+                                type="number"
+                                min={10}
+                                value={localConfig.auto_repeat.interval_ms}
+                                onChange={(e) => setRepeat({ interval_ms: parseInt(e.target.value) })}
+                            />
+                        </div>
                     </div>
 
                     {Object.entries(INSTRUMENTS).map(([group, keys]) => (
                         <div className="setting-group" key={group}>
                             <div className="group-title">{group}</div>
-                            {keys.map(key => (
+                            {keys.map(key => {
+                                const binding = localConfig.key_bindings[key] || '';
+                                const physical = binding.startsWith(PHYSICAL_PREFIX);
+                                return (
                                 <div className="input-row" key={key}>
                                     <span className="input-label">
                                         {key.replace('drum_', '').replace('_', ' ').toUpperCase()}
                                     </span>
+                                    <input
+                                        type="checkbox"
+                                        title="Same physical key on every keyboard layout"
+                                        checked={physical}
+                                        onChange={(e) => handleKeyChange(key, e.target.checked
+                                            ? PHYSICAL_PREFIX + binding
+                                            : binding.slice(PHYSICAL_PREFIX.length))}
+                                    />
                                     <input
                                         className="input-field"
-                                        value={localConfig.key_bindings[key] || ''}
+                                        value={binding}
                                         onKeyDown={(e) => {
                                             e.preventDefault();
-                                            handleKeyChange(key, e.key);
+                                            handleKeyChange(key, bindingName(e, physical));
                                         }}
                                         readOnly
                                         placeholder="Press Key"
                                     />
+                                    {/* For keys the webview never sees, e.g. media keys */}
+                                    <select
+                                        className="input-field"
+                                        style={{ width: 'auto' }}
+                                        value=""
+                                        onChange={(e) => handleKeyChange(key, (physical ? PHYSICAL_PREFIX : '') + e.target.value)}
+                                    >
+                                        <option value="">…</option>
+                                        {keyGroups.map(group => (
+                                            <optgroup label={group.label} key={group.category}>
+                                                {group.keys.map(k => (
+                                                    <option value={k.name} key={k.name}>{k.name}</option>
+                                                ))}
+                                            </optgroup>
+                                        ))}
+                                    </select>
                                 </div>
-                            ))}
+                                );
+                            })}
                         </div>
                     ))}
                 </div>
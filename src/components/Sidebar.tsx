@@ -5,14 +5,17 @@ interface Props {
     port: number;
     pin: string;
     qrCode: string;
+    pairingCode: string;
     onStart: () => void;
     onStop: () => void;
+    onRotatePin: () => void;
+    onPairingCode: () => void;
     onSettings: () => void;
 }
 
 export default function Sidebar({
-    serverRunning, clientCount, ip, port, pin, qrCode,
-    onStart, onStop, onSettings
+    serverRunning, clientCount, ip, port, pin, qrCode, pairingCode,
+    onStart, onStop, onRotatePin, onPairingCode, onSettings
 }: Props) {
     return (
         <div className="sidebar-col">
@@ -37,6 +40,13 @@ export default function Sidebar({
                         <span className="info-label">PIN</span>
                         <span className="info-value">{pin}</span>
 
+                        {pairingCode && (
+                            <>
+                                <span className="info-label">Code</span>
+                                <span className="info-value accent-text">{pairingCode}</span>
+                            </>
+                        )}
+
                         <span className="info-label">Clients</span>
                         <span className="info-value accent-text">{clientCount}</span>
                     </div>
@@ -67,6 +77,18 @@ export default function Sidebar({
                         </button>
                     )}
 
+                    {serverRunning && (
+                        <button className="control-btn btn-settings" onClick={onRotatePin}>
+                            ↻ NEW PIN
+                        </button>
+                    )}
+
+                    {serverRunning && (
+                        <button className="control-btn btn-settings" onClick={onPairingCode}>
+                            # PAIRING CODE
+                        </button>
+                    )}
+
                     <button className="control-btn btn-settings" onClick={onSettings}>
                         ⚙ SETTINGS
                     </button>